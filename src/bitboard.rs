@@ -0,0 +1,270 @@
+use std::fmt;
+use std::ops::{BitAnd, BitAndAssign, BitOr, BitOrAssign, BitXor, BitXorAssign, Not};
+use std::str::FromStr;
+
+use crate::movegen::{COLUMN_LEFT, COLUMN_RIGHT, ROW_BOTTOM};
+
+/// A tile on the board, stored as its bit index in a bitboard: `y * 8 + 7 - x`,
+/// so a1 is bit 7 and h8 is bit 56.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Square(u8);
+
+impl Square {
+    /// square at (x, y), with (0, 0) being a1
+    pub const fn new(x: u8, y: u8) -> Self {
+        debug_assert!(x < 8 && y < 8);
+        Square(y * 8 + 7 - x)
+    }
+
+    pub const fn from_index(index: u8) -> Self {
+        debug_assert!(index < 64);
+        Square(index)
+    }
+
+    pub const fn index(self) -> u8 {
+        self.0
+    }
+
+    pub const fn x(self) -> u8 {
+        7 - self.0 % 8
+    }
+
+    pub const fn y(self) -> u8 {
+        self.0 / 8
+    }
+
+    pub const fn coords(self) -> (u8, u8) {
+        (self.x(), self.y())
+    }
+
+    pub const fn bb(self) -> Bitboard {
+        Bitboard(1 << self.0)
+    }
+
+    /// the neighbouring square in a direction, if it's still on the board
+    pub const fn shift(self, direction: Direction) -> Option<Square> {
+        let bb = self.bb().shift(direction);
+        if bb.is_empty() { None } else { Some(Square(bb.0.trailing_zeros() as u8)) }
+    }
+}
+
+impl From<(u8, u8)> for Square {
+    fn from((x, y): (u8, u8)) -> Self {
+        Square::new(x, y)
+    }
+}
+
+/// The string isn't an algebraic square name such as "e4"
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseSquareError;
+
+impl fmt::Display for ParseSquareError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid square name")
+    }
+}
+
+impl std::error::Error for ParseSquareError {}
+
+impl FromStr for Square {
+    type Err = ParseSquareError;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        let &[file, rank] = name.as_bytes() else {
+            return Err(ParseSquareError);
+        };
+        if !(b'a'..=b'h').contains(&file) || !(b'1'..=b'8').contains(&rank) {
+            return Err(ParseSquareError);
+        }
+
+        Ok(Square::new(file - b'a', rank - b'1'))
+    }
+}
+
+impl fmt::Display for Square {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}", (b'a' + self.x()) as char, (b'1' + self.y()) as char)
+    }
+}
+
+impl fmt::Debug for Square {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Square({self})")
+    }
+}
+
+/// Directions a bitboard can be shifted in, as seen from white's side of the board
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Direction {
+    Up,
+    Down,
+    Left,
+    Right,
+    UpLeft,
+    UpRight,
+    DownLeft,
+    DownRight,
+}
+
+impl Direction {
+    pub const fn opposite(self) -> Direction {
+        match self {
+            Direction::Up => Direction::Down,
+            Direction::Down => Direction::Up,
+            Direction::Left => Direction::Right,
+            Direction::Right => Direction::Left,
+            Direction::UpLeft => Direction::DownRight,
+            Direction::UpRight => Direction::DownLeft,
+            Direction::DownLeft => Direction::UpRight,
+            Direction::DownRight => Direction::UpLeft,
+        }
+    }
+}
+
+/// A set of squares, one bit per tile
+#[derive(Clone, Copy, PartialEq, Eq, Default, Hash)]
+pub struct Bitboard(pub u64);
+
+impl Bitboard {
+    pub const EMPTY: Bitboard = Bitboard(0);
+    pub const FULL: Bitboard = Bitboard(u64::MAX);
+
+    /// every square with the given x, 0 being the a-file
+    pub const fn file(x: u8) -> Bitboard {
+        Bitboard(COLUMN_LEFT >> x)
+    }
+
+    /// every square with the given y, 0 being the first rank
+    pub const fn rank(y: u8) -> Bitboard {
+        Bitboard(ROW_BOTTOM << (y * 8))
+    }
+
+    pub const fn count(self) -> u32 {
+        self.0.count_ones()
+    }
+
+    pub const fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    pub const fn contains(self, square: Square) -> bool {
+        self.0 & (1 << square.0) != 0
+    }
+
+    /// lowest set square, if any
+    pub fn first(self) -> Option<Square> {
+        self.into_iter().next()
+    }
+
+    /// move every square one step in a direction, dropping the ones that fall off the board
+    /// instead of wrapping them onto the next row
+    pub const fn shift(self, direction: Direction) -> Bitboard {
+        // the a-file is the high bit of each row, so moving right is a right shift
+        let bb = self.0;
+        Bitboard(match direction {
+            Direction::Up => bb << 8,
+            Direction::Down => bb >> 8,
+            Direction::Left => (bb << 1) & !COLUMN_RIGHT,
+            Direction::Right => (bb >> 1) & !COLUMN_LEFT,
+            Direction::UpLeft => (bb << 9) & !COLUMN_RIGHT,
+            Direction::UpRight => (bb << 7) & !COLUMN_LEFT,
+            Direction::DownLeft => (bb >> 7) & !COLUMN_RIGHT,
+            Direction::DownRight => (bb >> 9) & !COLUMN_LEFT,
+        })
+    }
+}
+
+impl From<Square> for Bitboard {
+    fn from(square: Square) -> Self {
+        square.bb()
+    }
+}
+
+impl fmt::Debug for Bitboard {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Bitboard({:#018x})", self.0)
+    }
+}
+
+impl fmt::Display for Bitboard {
+    /// the board drawn rank 8 first, like the chessboard! macro
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for y in (0..8).rev() {
+            for x in 0..8 {
+                write!(f, "{}", if self.contains(Square::new(x, y)) { '1' } else { '.' })?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+impl Not for Bitboard {
+    type Output = Bitboard;
+
+    fn not(self) -> Bitboard {
+        Bitboard(!self.0)
+    }
+}
+
+// binary ops take anything that converts to a bitboard, so squares can be mixed in directly
+macro_rules! bitboard_op {
+    ($op:ident, $fn:ident, $assign_op:ident, $assign_fn:ident) => {
+        impl<T: Into<Bitboard>> $op<T> for Bitboard {
+            type Output = Bitboard;
+
+            fn $fn(self, rhs: T) -> Bitboard {
+                Bitboard(self.0.$fn(rhs.into().0))
+            }
+        }
+
+        impl<T: Into<Bitboard>> $assign_op<T> for Bitboard {
+            fn $assign_fn(&mut self, rhs: T) {
+                self.0.$assign_fn(rhs.into().0);
+            }
+        }
+    };
+}
+
+bitboard_op!(BitAnd, bitand, BitAndAssign, bitand_assign);
+bitboard_op!(BitOr, bitor, BitOrAssign, bitor_assign);
+bitboard_op!(BitXor, bitxor, BitXorAssign, bitxor_assign);
+
+/// Iterator over the set squares of a bitboard, lowest bit first
+pub struct Squares(u64);
+
+impl Iterator for Squares {
+    type Item = Square;
+
+    fn next(&mut self) -> Option<Square> {
+        if self.0 == 0 {
+            return None;
+        }
+
+        let square = Square(self.0.trailing_zeros() as u8);
+        self.0 &= self.0 - 1;
+        Some(square)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let count = self.0.count_ones() as usize;
+        (count, Some(count))
+    }
+}
+
+impl ExactSizeIterator for Squares {}
+
+impl IntoIterator for Bitboard {
+    type Item = Square;
+    type IntoIter = Squares;
+
+    fn into_iter(self) -> Squares {
+        Squares(self.0)
+    }
+}
+
+impl FromIterator<Square> for Bitboard {
+    fn from_iter<I: IntoIterator<Item = Square>>(squares: I) -> Self {
+        squares.into_iter().fold(Bitboard::EMPTY, |bb, square| bb | square)
+    }
+}
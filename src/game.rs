@@ -1,10 +1,11 @@
 use arrayvec::*;
 use serde::{Serialize, Deserialize};
+use std::fmt;
 use std::fs::{self, File};
 use std::path::Path;
 use std::io::{BufReader, BufWriter};
 
-use crate::{magic::MagicTable, movegen::*};
+use crate::{bitboard::*, magic::MagicTable, movegen::*, zobrist};
 
 // thanks https://stackoverflow.com/questions/47582781/multi-line-integer-literals-in-rust
 #[macro_export]
@@ -21,14 +22,23 @@ macro_rules! chessboard {
     };
 }
 
-#[derive(PartialEq)]
-enum Color {
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Color {
     Black,
     White,
 }
 
-#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
-enum PieceType {
+impl Color {
+    pub fn other(self) -> Color {
+        match self {
+            Color::Black => Color::White,
+            Color::White => Color::Black,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum PieceType {
     King,
     Queen,
     Rook,
@@ -37,47 +47,389 @@ enum PieceType {
     Pawn,
 }
 
+impl PieceType {
+    pub const ALL: [PieceType; 6] = [
+        PieceType::King,
+        PieceType::Queen,
+        PieceType::Rook,
+        PieceType::Bishop,
+        PieceType::Horses,
+        PieceType::Pawn,
+    ];
+}
+
+/// Which moves a generator produces. Captures and Quiets split All between them: queen
+/// promotions count as captures, underpromotions as quiet moves even when they capture.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GenType {
+    Captures,
+    Quiets,
+    /// moves that capture the checker or block it, and king moves. Same as All when not in check
+    Evasions,
+    All,
+}
+
+/// A piece of a given color, as it stands on the board
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Piece {
+    pub color: Color,
+    pub kind: PieceType,
+}
+
+impl Piece {
+    pub fn new(color: Color, kind: PieceType) -> Self {
+        Piece { color, kind }
+    }
+
+    /// FEN character of the piece, uppercase for white
+    pub fn to_char(self) -> char {
+        let ch = match self.kind {
+            PieceType::King => 'k',
+            PieceType::Queen => 'q',
+            PieceType::Rook => 'r',
+            PieceType::Bishop => 'b',
+            PieceType::Horses => 'n',
+            PieceType::Pawn => 'p',
+        };
+
+        if self.color == Color::White { ch.to_ascii_uppercase() } else { ch }
+    }
+}
+
 pub struct GameState {
     turn: Color,
 
     magics_straight: Option<[[MagicTable; 8]; 8]>,
     magics_diagonal: Option<[[MagicTable; 8]; 8]>,
 
-    kings: u64,
-    queens: u64,
-    rooks: u64,
-    bishops: u64,
-    knights: u64,
-    pawns: u64,
+    kings: Bitboard,
+    queens: Bitboard,
+    rooks: Bitboard,
+    bishops: Bitboard,
+    knights: Bitboard,
+    pawns: Bitboard,
     /// positions of all pieces of a given color
-    black: u64,
-    white: u64,
-    // bitboard representing any king move, en passe
-    en_passe: u64,
-    short_castle: bool,
-    long_castle: bool,
+    black: Bitboard,
+    white: Bitboard,
+    // bitboard of the square a pawn can capture onto en passe, if any
+    en_passe: Bitboard,
+    // castling rights, indexed by color
+    short_castle: [bool; 2],
+    long_castle: [bool; 2],
+    // plies since the last capture or pawn move
+    halfmove_clock: u16,
+    fullmove_number: u16,
+    // one entry per move made, popped by unmake_move
+    history: Vec<Undo>,
+    // zobrist key of the position, kept up to date by make_move and unmake_move
+    key: u64,
+    // the same, but for nothing other than the pawns
+    pawn_key: u64,
     // mask of threat lines of all enemy pieces generated at the beginning of each turn
     // idk if I need this actually
     // threats: u64,
 }
 
-#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone)]
-pub struct Move {
-    src: (u8, u8),
-    dest: (u8, u8),
-    promo: Option<PieceType>,
-    castle: bool,
+/// A move packed into 16 bits: source square in bits 0-5, destination in bits 6-11 and a
+/// flag nibble on top. Squares are stored by their bitboard index.
+///
+/// Two moves are equal when they have the same squares and promotion piece. The capture,
+/// en passe and castle flags follow from the position, so `Move::new` matches the generated
+/// move whatever kind it is.
+#[derive(Clone, Copy)]
+pub struct Move(u16);
+
+impl PartialEq for Move {
+    fn eq(&self, other: &Self) -> bool {
+        self.identity() == other.identity()
+    }
+}
+
+impl Eq for Move {}
+
+impl PartialOrd for Move {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Move {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.identity().cmp(&other.identity())
+    }
+}
+
+impl std::hash::Hash for Move {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.identity().hash(state);
+    }
+}
+
+/// Most legal moves any chess position has
+pub const MAX_MOVES: usize = 218;
+pub type MoveList = ArrayVec<Move, MAX_MOVES>;
+
+/// State overwritten by make_move that can't be recovered from the move itself
+struct Undo {
+    mv: Move,
+    captured: Option<PieceType>,
+    en_passe: Bitboard,
+    short_castle: [bool; 2],
+    long_castle: [bool; 2],
+    halfmove_clock: u16,
+    fullmove_number: u16,
+    key: u64,
+    pawn_key: u64,
 }
 
 impl Move {
-    pub fn new(src: (u8, u8), dest: (u8, u8)) -> Self {
-        Move {
-            src: src,
-            dest: dest,
-            promo: None,
-            castle: false,
+    // flag nibble, laid out so the capture and promotion bits can be tested on their own
+    const QUIET: u16 = 0;
+    const KING_CASTLE: u16 = 2;
+    const QUEEN_CASTLE: u16 = 3;
+    const CAPTURE: u16 = 4;
+    const EN_PASSE: u16 = 5;
+    const PROMO: u16 = 8;
+
+    fn pack(src: impl Into<Square>, dest: impl Into<Square>, flags: u16) -> Self {
+        let src = src.into().index() as u16;
+        let dest = dest.into().index() as u16;
+        Move(src | (dest << 6) | (flags << 12))
+    }
+
+    /// a move that doesn't capture anything
+    pub fn new(src: impl Into<Square>, dest: impl Into<Square>) -> Self {
+        Self::pack(src, dest, Self::QUIET)
+    }
+
+    pub fn capture(src: impl Into<Square>, dest: impl Into<Square>) -> Self {
+        Self::pack(src, dest, Self::CAPTURE)
+    }
+
+    pub fn en_passe(src: impl Into<Square>, dest: impl Into<Square>) -> Self {
+        Self::pack(src, dest, Self::EN_PASSE)
+    }
+
+    /// the king's move of a castle, which side is taken from the direction it goes
+    pub fn castle(src: impl Into<Square>, dest: impl Into<Square>) -> Self {
+        let (src, dest) = (src.into(), dest.into());
+        let flags = if dest.x() > src.x() { Self::KING_CASTLE } else { Self::QUEEN_CASTLE };
+        Self::pack(src, dest, flags)
+    }
+
+    /// a pawn turning into `promo`, or None when that's a king or another pawn
+    pub fn promotion(src: impl Into<Square>, dest: impl Into<Square>, promo: PieceType, capture: bool) -> Option<Self> {
+        let piece = match promo {
+            PieceType::Horses => 0,
+            PieceType::Bishop => 1,
+            PieceType::Rook => 2,
+            PieceType::Queen => 3,
+            PieceType::King | PieceType::Pawn => return None,
+        };
+        let capture = if capture { Self::CAPTURE } else { 0 };
+        Some(Self::pack(src, dest, Self::PROMO | capture | piece))
+    }
+
+    fn flags(&self) -> u16 {
+        self.0 >> 12
+    }
+
+    /// the bits that say which move it is: the squares, and the piece for promotions
+    fn identity(&self) -> u16 {
+        if self.is_promo() { self.0 & !(Self::CAPTURE << 12) } else { self.0 & 0xFFF }
+    }
+
+    /// the packed move, for tables that store it as a plain integer
+    pub(crate) fn to_bits(self) -> u16 {
+        self.0
+    }
+
+    /// nothing is checked, the move may not even make sense for any position
+    pub(crate) fn from_bits(bits: u16) -> Self {
+        Move(bits)
+    }
+
+    pub fn src(&self) -> Square {
+        Square::from_index((self.0 & 0x3F) as u8)
+    }
+
+    pub fn dest(&self) -> Square {
+        Square::from_index(((self.0 >> 6) & 0x3F) as u8)
+    }
+
+    pub fn promo(&self) -> Option<PieceType> {
+        if !self.is_promo() {
+            return None;
+        }
+
+        Some(match self.flags() & 0b11 {
+            0 => PieceType::Horses,
+            1 => PieceType::Bishop,
+            2 => PieceType::Rook,
+            _ => PieceType::Queen,
+        })
+    }
+
+    /// en passe and capturing promotions count as captures too
+    pub fn is_capture(&self) -> bool {
+        self.flags() & Self::CAPTURE != 0
+    }
+
+    pub fn is_en_passe(&self) -> bool {
+        self.flags() == Self::EN_PASSE
+    }
+
+    pub fn is_castle(&self) -> bool {
+        self.flags() == Self::KING_CASTLE || self.flags() == Self::QUEEN_CASTLE
+    }
+
+    pub fn is_promo(&self) -> bool {
+        self.flags() & Self::PROMO != 0
+    }
+
+    /// the move as UCI writes it, same as its Display
+    pub fn to_uci(&self) -> String {
+        self.to_string()
+    }
+}
+
+impl fmt::Debug for Move {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Move({self}")?;
+        if self.is_castle() {
+            write!(f, ", castle")?;
+        } else if self.is_en_passe() {
+            write!(f, ", en passe")?;
+        } else if self.is_capture() {
+            write!(f, ", capture")?;
+        }
+        write!(f, ")")
+    }
+}
+
+impl fmt::Display for Move {
+    /// long algebraic coordinates, e.g. "e2e4" or "e7e8q"
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}", self.src(), self.dest())?;
+        match self.promo() {
+            Some(PieceType::Queen) => write!(f, "q"),
+            Some(PieceType::Rook) => write!(f, "r"),
+            Some(PieceType::Bishop) => write!(f, "b"),
+            Some(PieceType::Horses) => write!(f, "n"),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Reasons a FEN string can be rejected. Positions are byte offsets into the FEN string.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FenError {
+    /// the string has no fields at all
+    Empty,
+    /// the piece placement doesn't describe exactly 8 ranks
+    RankCount { found: usize },
+    /// a rank describes more than 8 squares
+    RankOverflow { rank: u8, position: usize },
+    /// a rank describes fewer than 8 squares
+    RankIncomplete { rank: u8, position: usize },
+    /// a character in the piece placement that isn't a piece or a 1-8 digit
+    BadPiece { ch: char, position: usize },
+    /// a pawn on the first or eighth rank
+    PawnOnBackRank { position: usize },
+    /// a side doesn't have exactly one king
+    KingCount { white: bool, found: u32 },
+    /// side to move isn't "w" or "b"
+    BadSideToMove { position: usize },
+    /// a character in the castling field that isn't one of "KQkq"
+    BadCastling { ch: char, position: usize },
+    /// en passant target isn't "-" or a square on the sixth rank of the side to move
+    BadEnPassant { position: usize },
+    /// halfmove clock or fullmove number isn't a number
+    BadClock { position: usize },
+    /// more than six fields
+    TrailingField { position: usize },
+}
+
+impl fmt::Display for FenError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FenError::Empty => write!(f, "empty FEN string"),
+            FenError::RankCount { found } => write!(f, "expected 8 ranks in piece placement, found {found}"),
+            FenError::RankOverflow { rank, position } => write!(f, "rank {rank} has more than 8 squares (at position {position})"),
+            FenError::RankIncomplete { rank, position } => write!(f, "rank {rank} has fewer than 8 squares (at position {position})"),
+            FenError::BadPiece { ch, position } => write!(f, "invalid piece character '{ch}' at position {position}"),
+            FenError::PawnOnBackRank { position } => write!(f, "pawn on back rank at position {position}"),
+            FenError::KingCount { white, found } => {
+                let side = if *white { "white" } else { "black" };
+                write!(f, "{side} has {found} kings, expected 1")
+            },
+            FenError::BadSideToMove { position } => write!(f, "invalid side to move at position {position}"),
+            FenError::BadCastling { ch, position } => write!(f, "invalid castling character '{ch}' at position {position}"),
+            FenError::BadEnPassant { position } => write!(f, "invalid en passant square at position {position}"),
+            FenError::BadClock { position } => write!(f, "invalid move clock at position {position}"),
+            FenError::TrailingField { position } => write!(f, "unexpected trailing field at position {position}"),
+        }
+    }
+}
+
+impl std::error::Error for FenError {}
+
+/// Reasons a move string can't be turned into a move
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MoveParseError {
+    /// the string isn't written the way the notation expects
+    Malformed,
+    /// well formed, but no legal move matches it
+    Illegal,
+    /// more than one legal move matches it
+    Ambiguous,
+}
+
+impl fmt::Display for MoveParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MoveParseError::Malformed => write!(f, "malformed move"),
+            MoveParseError::Illegal => write!(f, "illegal move"),
+            MoveParseError::Ambiguous => write!(f, "ambiguous move"),
+        }
+    }
+}
+
+impl std::error::Error for MoveParseError {}
+
+/// Ways a game can end
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum GameResult {
+    Checkmate { winner: Color },
+    Stalemate,
+    ThreefoldRepetition,
+    FiftyMoveRule,
+    InsufficientMaterial,
+}
+
+impl GameResult {
+    pub fn winner(self) -> Option<Color> {
+        match self {
+            GameResult::Checkmate { winner } => Some(winner),
+            _ => None,
         }
     }
+
+    pub fn is_draw(self) -> bool {
+        self.winner().is_none()
+    }
+}
+
+impl Default for GameState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Display for GameState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.to_fen())
+    }
 }
 
 #[derive(Serialize, Deserialize)]
@@ -87,7 +439,8 @@ struct MagicsDb {
 }
 impl GameState {
 
-    const MAGICS_FILE_PATH: &str = "magics/magics.db";
+    // bump the version whenever the tables change so stale databases get regenerated
+    const MAGICS_FILE_PATH: &str = "magics/magics_v2.db";
 
     pub fn new() -> GameState {
         const KINGS_INIT: u64 = chessboard!(
@@ -178,94 +531,174 @@ impl GameState {
             0b_11111111
         );
 
-        GameState {
+        let mut game = GameState {
             turn: Color::White,
             magics_diagonal: None,
             magics_straight: None,
-            kings:  KINGS_INIT,
-            queens: QUEENS_INIT,
-            rooks: ROOKS_INIT,
-            knights: KNIGHTS_INIT,
-            bishops: BISHOPS_INIT,
-            pawns: PAWNS_INIT,
-            black: BLACK_SIDE,
-            white: WHITE_SIDE,
-            en_passe: 0,
-            short_castle: true,
-            long_castle: true,
-        }
-    }
-
-    // primarily used for tests
-    // TODO: read castling,
-    pub fn try_from_fen(fen: &str) -> Result<Self, ()> {
-
-        let mut kings: u64 = 0;
-        let mut queens: u64 = 0;
-        let mut rooks: u64 = 0;
-        let mut bishops: u64 = 0;
-        let mut knights: u64 = 0;
-        let mut pawns: u64 = 0;
-        let mut black: u64 = 0;
-        let mut white: u64 = 0;
+            kings:  Bitboard(KINGS_INIT),
+            queens: Bitboard(QUEENS_INIT),
+            rooks: Bitboard(ROOKS_INIT),
+            knights: Bitboard(KNIGHTS_INIT),
+            bishops: Bitboard(BISHOPS_INIT),
+            pawns: Bitboard(PAWNS_INIT),
+            black: Bitboard(BLACK_SIDE),
+            white: Bitboard(WHITE_SIDE),
+            en_passe: Bitboard::EMPTY,
+            short_castle: [true; 2],
+            long_castle: [true; 2],
+            halfmove_clock: 0,
+            fullmove_number: 1,
+            history: Vec::new(),
+            key: 0,
+            pawn_key: 0,
+        };
+        game.key = game.compute_key();
+        game.pawn_key = game.compute_pawn_key();
+        game
+    }
+
+    /// Parse a position from FEN. Trailing fields after the piece placement may be
+    /// omitted, in which case they default to "w - - 0 1".
+    pub fn try_from_fen(fen: &str) -> Result<Self, FenError> {
+        // pair each field with its byte offset into the string so errors can point at it
+        let mut fields = fen
+            .split_whitespace()
+            .map(|field| (field.as_ptr() as usize - fen.as_ptr() as usize, field));
+        let (_, placement) = fields.next().ok_or(FenError::Empty)?;
+
+        let mut kings = Bitboard::EMPTY;
+        let mut queens = Bitboard::EMPTY;
+        let mut rooks = Bitboard::EMPTY;
+        let mut bishops = Bitboard::EMPTY;
+        let mut knights = Bitboard::EMPTY;
+        let mut pawns = Bitboard::EMPTY;
+        let mut black = Bitboard::EMPTY;
+        let mut white = Bitboard::EMPTY;
 
         // FEN is read top-to-bottom (rank 8 first), so row 0 = rank 8 = y=7
         let mut fen_row: u8 = 0;
+        let mut offset = 0;
 
-        for row_contents in fen.split('/') {
+        for row_contents in placement.split('/') {
             if fen_row > 7 {
-                return Err(())
+                return Err(FenError::RankCount { found: placement.split('/').count() })
             }
             // Convert FEN row (0=rank8, 7=rank1) to y coordinate (0=rank1, 7=rank8)
             let y = 7 - fen_row;
+            let rank = y + 1;
             let mut col: u8 = 0;
 
-            for ch in row_contents.chars() {
-                let color: &mut u64 = if ch.is_lowercase() { &mut black } else { &mut white };
+            for (idx, ch) in row_contents.char_indices() {
+                let position = offset + idx;
+                let color: &mut Bitboard = if ch.is_lowercase() { &mut black } else { &mut white };
 
-                if col > 7 { return Err(()); }
+                if col > 7 { return Err(FenError::RankOverflow { rank, position }); }
                 if let Some(space) = ch.to_digit(10) {
+                    if space == 0 {
+                        return Err(FenError::BadPiece { ch, position });
+                    }
                     col += space as u8;
+                    if col > 8 { return Err(FenError::RankOverflow { rank, position }); }
                     continue;
                 }
 
                 match ch.to_ascii_lowercase() {
                     'k' => {
-                        kings |= coords_to_bb(col, y);
+                        kings |= Square::new(col, y);
                     },
                     'q' => {
-                        queens |= coords_to_bb(col, y);
+                        queens |= Square::new(col, y);
                     },
                     'r' => {
-                        rooks |= coords_to_bb(col, y);
+                        rooks |= Square::new(col, y);
                     },
                     'b' => {
-                        bishops |= coords_to_bb(col, y);
+                        bishops |= Square::new(col, y);
                     },
                     'n' => {
-                        knights |= coords_to_bb(col, y);
+                        knights |= Square::new(col, y);
                     },
                     'p' => {
-                        pawns |= coords_to_bb(col, y);
+                        if y == 0 || y == 7 {
+                            return Err(FenError::PawnOnBackRank { position });
+                        }
+                        pawns |= Square::new(col, y);
                     },
                     _ => {
-                        return Err(())
+                        return Err(FenError::BadPiece { ch, position })
                     }
                 }
 
-                *color |= coords_to_bb(col, y);
+                *color |= Square::new(col, y);
                 col += 1;
             }
 
-            if col != 8 { return Err(()); }
+            if col != 8 {
+                return Err(FenError::RankIncomplete { rank, position: offset + row_contents.len() });
+            }
 
+            offset += row_contents.len() + 1;
             fen_row += 1;
         }
 
-        if fen_row != 8 { return Err(()) }
+        if fen_row != 8 { return Err(FenError::RankCount { found: fen_row as usize }) }
 
-        Ok(GameState {
-            turn: Color::White,
+        for (is_white, side) in [(true, white), (false, black)] {
+            let found = (kings & side).count();
+            if found != 1 {
+                return Err(FenError::KingCount { white: is_white, found });
+            }
+        }
+
+        let turn = match fields.next() {
+            None | Some((_, "w")) => Color::White,
+            Some((_, "b")) => Color::Black,
+            Some((position, _)) => return Err(FenError::BadSideToMove { position }),
+        };
+
+        let mut short_castle = [false; 2];
+        let mut long_castle = [false; 2];
+        match fields.next() {
+            None | Some((_, "-")) => {},
+            Some((offset, castling)) => {
+                for (idx, ch) in castling.char_indices() {
+                    match ch {
+                        'K' => short_castle[Color::White as usize] = true,
+                        'Q' => long_castle[Color::White as usize] = true,
+                        'k' => short_castle[Color::Black as usize] = true,
+                        'q' => long_castle[Color::Black as usize] = true,
+                        _ => return Err(FenError::BadCastling { ch, position: offset + idx }),
+                    }
+                }
+            }
+        }
+
+        let en_passe = match fields.next() {
+            None | Some((_, "-")) => Bitboard::EMPTY,
+            Some((position, square)) => {
+                let square: Square = square.parse().map_err(|_| FenError::BadEnPassant { position })?;
+                // the target square always sits behind a pawn that just double pushed
+                let expected_y = if turn == Color::White { 5 } else { 2 };
+                if square.y() != expected_y { return Err(FenError::BadEnPassant { position }); }
+                square.bb()
+            }
+        };
+
+        let halfmove_clock = match fields.next() {
+            None => 0,
+            Some((position, clock)) => clock.parse::<u16>().map_err(|_| FenError::BadClock { position })?,
+        };
+        let fullmove_number = match fields.next() {
+            None => 1,
+            Some((position, clock)) => clock.parse::<u16>().map_err(|_| FenError::BadClock { position })?,
+        };
+
+        if let Some((position, _)) = fields.next() {
+            return Err(FenError::TrailingField { position });
+        }
+
+        let mut game = GameState {
+            turn,
             magics_straight: None,
             magics_diagonal: None,
             kings,
@@ -276,13 +709,366 @@ impl GameState {
             pawns,
             black,
             white,
-            // handle proper initialization of these special fields later
-            en_passe: 0u64,
-            short_castle: true,
-            long_castle: true,
-        })
+            en_passe,
+            short_castle,
+            long_castle,
+            halfmove_clock,
+            fullmove_number,
+            history: Vec::new(),
+            key: 0,
+            pawn_key: 0,
+        };
+        game.key = game.compute_key();
+        game.pawn_key = game.compute_pawn_key();
+        Ok(game)
+    }
+
+    /// Replace the position with one parsed from FEN, keeping the magic tables already loaded.
+    /// The position is left untouched if the FEN is rejected.
+    pub fn set_fen(&mut self, fen: &str) -> Result<(), FenError> {
+        let mut game = Self::try_from_fen(fen)?;
+        game.magics_straight = self.magics_straight.take();
+        game.magics_diagonal = self.magics_diagonal.take();
+        *self = game;
+        Ok(())
+    }
+
+    /// Serialize the position as a full six field FEN string
+    pub fn to_fen(&self) -> String {
+        let mut fen = String::new();
+
+        for y in (0..8).rev() {
+            let mut empty = 0;
+            for x in 0..8 {
+                let Some(piece) = self.piece_at(Square::new(x, y)) else {
+                    empty += 1;
+                    continue;
+                };
+
+                if empty > 0 {
+                    fen.push_str(&empty.to_string());
+                    empty = 0;
+                }
+                fen.push(piece.to_char());
+            }
+
+            if empty > 0 {
+                fen.push_str(&empty.to_string());
+            }
+            if y > 0 {
+                fen.push('/');
+            }
+        }
+
+        fen.push_str(if self.turn == Color::White { " w " } else { " b " });
+
+        let castling_start = fen.len();
+        if self.short_castle[Color::White as usize] { fen.push('K'); }
+        if self.long_castle[Color::White as usize] { fen.push('Q'); }
+        if self.short_castle[Color::Black as usize] { fen.push('k'); }
+        if self.long_castle[Color::Black as usize] { fen.push('q'); }
+        if fen.len() == castling_start { fen.push('-'); }
+
+        match self.en_passe.first() {
+            Some(square) => fen.push_str(&format!(" {square}")),
+            None => fen.push_str(" -"),
+        }
+
+        fen.push_str(&format!(" {} {}", self.halfmove_clock, self.fullmove_number));
+
+        fen
+    }
+
+    /// Apply a move for the side to move. The move is assumed to be at least pseudo-legal.
+    pub fn make_move(&mut self, mv: &Move) {
+        let (src, dest) = (mv.src(), mv.dest());
+        let piece = self.piece_type(src).expect("no piece on source square");
+
+        let mut undo = Undo {
+            mv: *mv,
+            captured: None,
+            en_passe: self.en_passe,
+            short_castle: self.short_castle,
+            long_castle: self.long_castle,
+            halfmove_clock: self.halfmove_clock,
+            fullmove_number: self.fullmove_number,
+            key: self.key,
+            pawn_key: self.pawn_key,
+        };
+
+        // rights and en passe are xored back in once they've been updated below
+        let us = self.turn;
+        self.key ^= self.castling_key() ^ self.en_passe_key();
+
+        // an en passe capture takes the pawn beside the source square rather than on the destination
+        let capture = if piece == PieceType::Pawn && self.en_passe.contains(dest) {
+            Square::new(dest.x(), src.y())
+        } else {
+            dest
+        };
+
+        if self.enemy_bb().contains(capture) {
+            let captured = self.piece_type(capture).unwrap();
+            *self.piece_bb_mut(captured) ^= capture;
+            *self.enemy_bb_mut() ^= capture;
+            self.key ^= Self::piece_key(us.other(), captured, capture);
+            if captured == PieceType::Pawn {
+                self.pawn_key ^= Self::piece_key(us.other(), captured, capture);
+            }
+            undo.captured = Some(captured);
+        }
+
+        *self.piece_bb_mut(piece) ^= src.bb() | dest;
+        *self.self_bb_mut() ^= src.bb() | dest;
+        self.key ^= Self::piece_key(us, piece, src) ^ Self::piece_key(us, piece, dest);
+        if piece == PieceType::Pawn {
+            self.pawn_key ^= Self::piece_key(us, piece, src) ^ Self::piece_key(us, piece, dest);
+        }
+
+        if let Some(promo) = mv.promo() {
+            self.pawns ^= dest;
+            *self.piece_bb_mut(promo) |= dest;
+            self.key ^= Self::piece_key(us, PieceType::Pawn, dest) ^ Self::piece_key(us, promo, dest);
+            self.pawn_key ^= Self::piece_key(us, PieceType::Pawn, dest);
+        }
+
+        // told apart by the king going two squares, so a move without the castle flag still castles
+        if piece == PieceType::King && src.x().abs_diff(dest.x()) == 2 {
+            let (rook_src, rook_dest) = Self::castle_rook_squares(mv);
+            self.rooks ^= rook_src.bb() | rook_dest;
+            *self.self_bb_mut() ^= rook_src.bb() | rook_dest;
+            self.key ^= Self::piece_key(us, PieceType::Rook, rook_src) ^ Self::piece_key(us, PieceType::Rook, rook_dest);
+        }
+
+        // moving the king or either rook off its corner, or capturing a rook on its corner, loses the right
+        let side = self.turn as usize;
+        if piece == PieceType::King {
+            self.short_castle[side] = false;
+            self.long_castle[side] = false;
+        }
+        for (corner, color, short) in [
+            (Square::new(7, 0), Color::White, true),
+            (Square::new(0, 0), Color::White, false),
+            (Square::new(7, 7), Color::Black, true),
+            (Square::new(0, 7), Color::Black, false),
+        ] {
+            if corner == src || corner == dest {
+                if short {
+                    self.short_castle[color as usize] = false;
+                } else {
+                    self.long_castle[color as usize] = false;
+                }
+            }
+        }
+
+        self.en_passe = if piece == PieceType::Pawn && src.y().abs_diff(dest.y()) == 2 {
+            Square::new(src.x(), (src.y() + dest.y()) / 2).bb()
+        } else {
+            Bitboard::EMPTY
+        };
+
+        if piece == PieceType::Pawn || undo.captured.is_some() {
+            self.halfmove_clock = 0;
+        } else {
+            // a FEN can start the clocks anywhere up to u16::MAX
+            self.halfmove_clock = self.halfmove_clock.saturating_add(1);
+        }
+        if self.turn == Color::Black {
+            self.fullmove_number = self.fullmove_number.saturating_add(1);
+        }
+
+        self.turn = self.turn.other();
+        self.key ^= zobrist::BLACK_TO_MOVE ^ self.castling_key() ^ self.en_passe_key();
+        self.history.push(undo);
+
+        debug_assert_eq!(self.key, self.compute_key(), "zobrist key out of sync after {mv}");
+        debug_assert_eq!(self.pawn_key, self.compute_pawn_key(), "pawn key out of sync after {mv}");
+    }
+
+    /// Take back the last move made with make_move, restoring the position exactly
+    pub fn unmake_move(&mut self) {
+        let undo = self.history.pop().expect("no move to unmake");
+        let mv = &undo.mv;
+
+        self.turn = self.turn.other();
+        self.halfmove_clock = undo.halfmove_clock;
+        self.fullmove_number = undo.fullmove_number;
+        self.short_castle = undo.short_castle;
+        self.long_castle = undo.long_castle;
+        self.en_passe = undo.en_passe;
+        self.key = undo.key;
+        self.pawn_key = undo.pawn_key;
+
+        let (src, dest) = (mv.src(), mv.dest());
+
+        if self.kings.contains(dest) && src.x().abs_diff(dest.x()) == 2 {
+            let (rook_src, rook_dest) = Self::castle_rook_squares(mv);
+            self.rooks ^= rook_src.bb() | rook_dest;
+            *self.self_bb_mut() ^= rook_src.bb() | rook_dest;
+        }
+
+        if let Some(promo) = mv.promo() {
+            *self.piece_bb_mut(promo) ^= dest;
+            self.pawns |= dest;
+        }
+
+        let piece = self.piece_type(dest).unwrap();
+        *self.piece_bb_mut(piece) ^= src.bb() | dest;
+        *self.self_bb_mut() ^= src.bb() | dest;
+
+        if let Some(captured) = undo.captured {
+            let capture = if piece == PieceType::Pawn && self.en_passe.contains(dest) {
+                Square::new(dest.x(), src.y())
+            } else {
+                dest
+            };
+            *self.piece_bb_mut(captured) |= capture;
+            *self.enemy_bb_mut() |= capture;
+        }
+
+        debug_assert_eq!(self.key, self.compute_key(), "zobrist key out of sync after taking back {mv}");
+        debug_assert_eq!(self.pawn_key, self.compute_pawn_key(), "pawn key out of sync after taking back {mv}");
+    }
+
+    /// Zobrist key of the position: pieces, side to move, castling rights and the en passe file.
+    /// The move clocks aren't part of it, so repeated positions share a key.
+    pub fn key(&self) -> u64 {
+        self.key
+    }
+
+    /// the zobrist key built up from scratch, which the incremental key has to match
+    pub fn compute_key(&self) -> u64 {
+        let mut key = 0;
+
+        for color in [Color::Black, Color::White] {
+            for kind in PieceType::ALL {
+                for square in self.colored_pieces(color, kind) {
+                    key ^= Self::piece_key(color, kind, square);
+                }
+            }
+        }
+
+        if self.turn == Color::Black {
+            key ^= zobrist::BLACK_TO_MOVE;
+        }
+
+        key ^ self.castling_key() ^ self.en_passe_key()
+    }
+
+    /// Zobrist key of where the pawns stand and nothing else, for caching pawn structure
+    pub fn pawn_key(&self) -> u64 {
+        self.pawn_key
+    }
+
+    /// the pawn key built up from scratch
+    pub fn compute_pawn_key(&self) -> u64 {
+        [Color::Black, Color::White]
+            .into_iter()
+            .flat_map(|color| self.colored_pieces(color, PieceType::Pawn).into_iter().map(move |square| (color, square)))
+            .fold(0, |key, (color, square)| key ^ Self::piece_key(color, PieceType::Pawn, square))
+    }
+
+    fn piece_key(color: Color, kind: PieceType, square: Square) -> u64 {
+        zobrist::PIECES[color as usize][kind as usize][square.index() as usize]
+    }
+
+    fn castling_key(&self) -> u64 {
+        let rights = [
+            self.short_castle[Color::White as usize],
+            self.long_castle[Color::White as usize],
+            self.short_castle[Color::Black as usize],
+            self.long_castle[Color::Black as usize],
+        ];
+
+        rights
+            .into_iter()
+            .zip(zobrist::CASTLING)
+            .filter(|(allowed, _)| *allowed)
+            .fold(0, |key, (_, right)| key ^ right)
     }
 
+    /// the en passe file only counts while a pawn of the side to move can actually take,
+    /// otherwise the position is no different from one without the double push
+    fn en_passe_key(&self) -> u64 {
+        let Some(target) = self.en_passe.first() else { return 0 };
+
+        let behind = if self.turn == Color::White {
+            [Direction::DownLeft, Direction::DownRight]
+        } else {
+            [Direction::UpLeft, Direction::UpRight]
+        };
+        let pawns = self.self_bb() & self.pawns;
+        if behind.iter().any(|&dir| !(target.bb().shift(dir) & pawns).is_empty()) {
+            zobrist::EN_PASSE[target.x() as usize]
+        } else {
+            0
+        }
+    }
+
+    /// (source, destination) squares of the rook that moves alongside a castling king
+    fn castle_rook_squares(mv: &Move) -> (Square, Square) {
+        let y = mv.src().y();
+        if mv.dest().x() > mv.src().x() {
+            (Square::new(7, y), Square::new(5, y))
+        } else {
+            (Square::new(0, y), Square::new(3, y))
+        }
+    }
+
+    /// type of the piece on a square, if any
+    fn piece_type(&self, square: Square) -> Option<PieceType> {
+        PieceType::ALL.into_iter().find(|&kind| self.pieces(kind).contains(square))
+    }
+
+    fn piece_bb_mut(&mut self, piece: PieceType) -> &mut Bitboard {
+        match piece {
+            PieceType::King => &mut self.kings,
+            PieceType::Queen => &mut self.queens,
+            PieceType::Rook => &mut self.rooks,
+            PieceType::Bishop => &mut self.bishops,
+            PieceType::Horses => &mut self.knights,
+            PieceType::Pawn => &mut self.pawns,
+        }
+    }
+
+    /// The piece standing on a square, if any
+    pub fn piece_at(&self, square: Square) -> Option<Piece> {
+        let kind = self.piece_type(square)?;
+        let color = if self.white.contains(square) { Color::White } else { Color::Black };
+        Some(Piece::new(color, kind))
+    }
+
+    /// Side to move
+    pub fn turn(&self) -> Color {
+        self.turn
+    }
+
+    /// All pieces of one type, of either color
+    pub fn pieces(&self, kind: PieceType) -> Bitboard {
+        match kind {
+            PieceType::King => self.kings,
+            PieceType::Queen => self.queens,
+            PieceType::Rook => self.rooks,
+            PieceType::Bishop => self.bishops,
+            PieceType::Horses => self.knights,
+            PieceType::Pawn => self.pawns,
+        }
+    }
+
+    /// All pieces of one color
+    pub fn color_bb(&self, color: Color) -> Bitboard {
+        if color == Color::White { self.white } else { self.black }
+    }
+
+    /// All pieces of one type and color
+    pub fn colored_pieces(&self, color: Color, kind: PieceType) -> Bitboard {
+        self.pieces(kind) & self.color_bb(color)
+    }
+
+    /// Every occupied square
+    pub fn occupied(&self) -> Bitboard {
+        self.black | self.white
+    }
 
     pub fn init_magics(&mut self) {
         if let Ok(magics_db) = Self::read_magics_db() {
@@ -307,8 +1093,8 @@ impl GameState {
             let mut writer = BufWriter::new(file);
 
             let magics_db = MagicsDb {
-                magics_straight: magics_straight,
-                magics_diagonal: magics_diagonal,
+                magics_straight,
+                magics_diagonal,
             };
 
             bincode::serialize_into(&mut writer, &magics_db).expect("Failed to serialize and write magics db");
@@ -328,18 +1114,18 @@ impl GameState {
     // Return order is from top to bottom, left to right formatted as (original_pos_bitboard, new_pos_bitboard)
     // implement pawn promotion later
 
-    fn self_bb(&self) -> u64 {
-        if self.turn == Color::White { self.white } else { self.black }
+    fn self_bb(&self) -> Bitboard {
+        self.color_bb(self.turn)
     }
 
-    fn enemy_bb(&self) -> u64 {
-        if self.turn == Color::White { self.black } else { self.white }
+    fn enemy_bb(&self) -> Bitboard {
+        self.color_bb(self.turn.other())
     }
-    fn self_bb_mut(&mut self) -> &mut u64 {
+    fn self_bb_mut(&mut self) -> &mut Bitboard {
         if self.turn == Color::White { &mut self.white } else { &mut self.black }
     }
 
-    fn enemy_bb_mut(&mut self) -> &mut u64 {
+    fn enemy_bb_mut(&mut self) -> &mut Bitboard {
         if self.turn == Color::White { &mut self.black } else { &mut self.white }
     }
 
@@ -383,144 +1169,709 @@ impl GameState {
     //     (moves, self_threat)
     // }
 
-    // let short_castle_ray: u64 = 0b00000110;
-    // let long_castle_ray: u64 = 0b01110000;
-    // if self.turn == Color::Black {
-    //     short_castle_ray <<= 8*7;
-    //     long_castle_ray <<= 8*7;
-    // }
-
-    // if self.short_castle && short_castle_ray & whole_board == 0 {
+    /// one move to every square in moves_bb, flagged as a capture where it lands on `captures`
+    pub fn moves_from_bb<const N: usize>(moves_bb: Bitboard, src: Square, captures: Bitboard) -> ArrayVec<Move, N> {
+        moves_bb
+            .into_iter()
+            .map(|dest| if captures.contains(dest) { Move::capture(src, dest) } else { Move::new(src, dest) })
+            .collect()
+    }
 
-    // }
-    /// use a bitboard as a
-    pub fn moves_from_bb<const N: usize>(mut moves_bb: u64, src: (u8, u8)) -> ArrayVec<Move, N> {
-        let mut moveset = ArrayVec::new();
+    /// rook rays from a square, each stopped by and including the first piece of `occupied` on it
+    fn straight_attacks(&self, square: Square, occupied: Bitboard) -> Bitboard {
+        let magics_straight = self.magics_straight.as_ref().unwrap();
+        Bitboard(magics_straight[square.x() as usize][square.y() as usize].get_ray(occupied.0).unwrap())
+    }
 
-        while moves_bb != 0 {
-            let shift = moves_bb.leading_zeros();
-            let dest = right_shift_to_coords(shift as u8);
-            moveset.push(Move::new(src, dest));
+    /// bishop rays from a square, each stopped by and including the first piece of `occupied` on it
+    fn diagonal_attacks(&self, square: Square, occupied: Bitboard) -> Bitboard {
+        let magics_diagonal = self.magics_diagonal.as_ref().unwrap();
+        Bitboard(magics_diagonal[square.x() as usize][square.y() as usize].get_ray(occupied.0).unwrap())
+    }
 
-            moves_bb ^= rs_to_bb(shift);
+    /// squares a piece other than the king may move to for a kind of generation
+    fn targets(&self, gen_type: GenType) -> Bitboard {
+        match gen_type {
+            GenType::Captures => self.enemy_bb(),
+            GenType::Quiets => !self.occupied(),
+            GenType::All => !self.self_bb(),
+            GenType::Evasions => {
+                let checkers = self.checkers();
+                match checkers.count() {
+                    0 => !self.self_bb(),
+                    1 => checkers | self.between(self.king_square(), checkers.first().unwrap()),
+                    // only the king can get out of double check
+                    _ => Bitboard::EMPTY,
+                }
+            }
         }
-
-        moveset
     }
 
-    // ignore whether or not we're in check for now
+    // ignore whether or not we're in check for now, besides evasions masking destinations
     // castling moves implemented in king moves
-    pub fn rook_moves(&self) -> ArrayVec<Move, 28> {
+    pub fn rook_moves(&self, gen_type: GenType) -> MoveList {
         let mut moveset = ArrayVec::new();
+        let occupied = self.black | self.white;
+        let targets = self.targets(gen_type);
 
-        let self_bb = self.self_bb();
-
-        let mut rooks_bb = self.self_bb() & self.rooks;
-
-        for _ in 0..2 {
-            if rooks_bb == 0 { break; }
-            let shift = rooks_bb.leading_zeros();
-            let src = right_shift_to_coords(shift as u8);
-
-            rooks_bb ^= rs_to_bb(shift);
-
-            let blockers = self.black | self.white & !coords_to_bb(src.0, src.1);
-            let magics_straight = self.magics_straight.as_ref().unwrap();
-            let mut moves_bb = magics_straight[src.0 as usize][src.1 as usize].get_ray(blockers).unwrap();
-
-            moves_bb &= !self_bb;
-
-            let curr_moves = Self::moves_from_bb::<14>(moves_bb, src);
-            moveset.extend(curr_moves);
+        for src in self.self_bb() & self.rooks {
+            let moves_bb = self.straight_attacks(src, occupied) & targets;
+            moveset.extend(Self::moves_from_bb::<14>(moves_bb, src, self.enemy_bb()));
         }
 
         moveset
     }
 
-    pub fn bishop_moves(&self) -> ArrayVec<Move, 26> {
+    pub fn bishop_moves(&self, gen_type: GenType) -> MoveList {
         let mut moveset = ArrayVec::new();
+        let occupied = self.black | self.white;
+        let targets = self.targets(gen_type);
 
-        let self_bb = self.self_bb();
-
-        let mut bishops_bb = self.self_bb() & self.bishops;
-
-        for _ in 0..2 {
-            if bishops_bb == 0 { break; }
-
-            let shift = bishops_bb.leading_zeros();
-            let src = right_shift_to_coords(shift as u8);
-
-            bishops_bb ^= rs_to_bb(shift);
-
-            let blockers = self.black | self.white & !coords_to_bb(src.0, src.1);
-            let magics_straight = self.magics_diagonal.as_ref().unwrap();
-            let mut moves_bb = magics_straight[src.0 as usize][src.1 as usize].get_ray(blockers).unwrap();
-
-            moves_bb &= !self_bb;
-            let curr_moves = Self::moves_from_bb::<13>(moves_bb, src);
-            moveset.extend(curr_moves);
+        for src in self.self_bb() & self.bishops {
+            let moves_bb = self.diagonal_attacks(src, occupied) & targets;
+            moveset.extend(Self::moves_from_bb::<13>(moves_bb, src, self.enemy_bb()));
         }
 
         moveset
     }
 
-    pub fn knight_moves(&self) -> ArrayVec<Move, 16> {
+    pub fn queen_moves(&self, gen_type: GenType) -> MoveList {
         let mut moveset = ArrayVec::new();
+        let occupied = self.black | self.white;
+        let targets = self.targets(gen_type);
 
-        let self_bb = self.self_bb();
-        let mut knights_bb = self.self_bb() & self.knights;
-
-        for _ in 0..2 {
-            if knights_bb == 0 { break; }
-            let shift = knights_bb.leading_zeros();
-            let src = right_shift_to_coords(shift as u8);
-
-            knights_bb ^= rs_to_bb(shift);
-            let mut moves_bb = gen_knight(src.0, src.1);
-            moves_bb &= !self_bb;
-            let curr_moves = Self::moves_from_bb::<8>(moves_bb, src);
-            moveset.extend(curr_moves);
+        for src in self.self_bb() & self.queens {
+            let moves_bb = (self.straight_attacks(src, occupied) | self.diagonal_attacks(src, occupied)) & targets;
+            moveset.extend(Self::moves_from_bb::<27>(moves_bb, src, self.enemy_bb()));
         }
 
         moveset
     }
 
-    // I think there's 30 possible pawn moves??
-    pub fn pawn_moves(&self) -> ArrayVec<Move, 30> {
-        let moveset = ArrayVec::new();
+    pub fn knight_moves(&self, gen_type: GenType) -> MoveList {
+        let mut moveset = ArrayVec::new();
+        let targets = self.targets(gen_type);
 
-        let self_bb = self.self_bb();
-        let mut pawns_bb = self_bb & self.pawns;
+        for src in self.self_bb() & self.knights {
+            let moves_bb = Bitboard(gen_knight(src.x(), src.y())) & targets;
+            moveset.extend(Self::moves_from_bb::<8>(moves_bb, src, self.enemy_bb()));
+        }
 
-        for _ in 0..8 {
-            if pawns_bb == 0 { break; }
+        moveset
+    }
 
-            let shift = self.pawns.leading_zeros();
-            let src = right_shift_to_coords(shift as u8);
+    pub fn pawn_moves(&self, gen_type: GenType) -> MoveList {
+        let mut moveset = ArrayVec::new();
 
-            pawns_bb &= !rs_to_bb(shift);
+        let white = self.turn == Color::White;
+        let (forward, capture_dirs) = if white {
+            (Direction::Up, [Direction::UpLeft, Direction::UpRight])
+        } else {
+            (Direction::Down, [Direction::DownLeft, Direction::DownRight])
+        };
+        let empty = !(self.black | self.white);
+        let pawns = self.self_bb() & self.pawns;
+        let promo_rank = Bitboard::rank(if white { 7 } else { 0 });
+
+        // captures only push to promote and quiets only capture to underpromote. Evasions leave
+        // en passe alone, the pawn it takes could be the checker
+        let (push_mask, capture_mask) = match gen_type {
+            GenType::Captures => (promo_rank, Bitboard::FULL),
+            GenType::Quiets => (Bitboard::FULL, promo_rank),
+            GenType::Evasions => {
+                let targets = self.targets(gen_type);
+                (targets, targets | self.en_passe)
+            }
+            GenType::All => (Bitboard::FULL, Bitboard::FULL),
+        };
+        let promos = match gen_type {
+            GenType::Captures => [PieceType::Queen].as_slice(),
+            GenType::Quiets => [PieceType::Rook, PieceType::Bishop, PieceType::Horses].as_slice(),
+            _ => [PieceType::Queen, PieceType::Rook, PieceType::Bishop, PieceType::Horses].as_slice(),
+        };
+
+        // all pawns step at once, then each destination is traced back to the pawn that got there
+        let single = pawns.shift(forward) & empty;
+        let double = (single & Bitboard::rank(if white { 2 } else { 5 })).shift(forward) & empty & push_mask;
+        let single = single & push_mask;
+
+        let capturable = (self.enemy_bb() | self.en_passe) & capture_mask;
+        let [left, right] = capture_dirs;
+        let targets = [
+            (single, forward, 1),
+            (double, forward, 2),
+            (pawns.shift(left) & capturable, left, 1),
+            (pawns.shift(right) & capturable, right, 1),
+        ];
 
+        for (dests, dir, steps) in targets {
+            for dest in dests {
+                let mut src = dest;
+                for _ in 0..steps {
+                    src = src.shift(dir.opposite()).unwrap();
+                }
+                let capture = dir != forward;
+
+                if promo_rank.contains(dest) {
+                    // promos only holds pieces a pawn can become, so every one of them is a move
+                    moveset.extend(promos.iter().filter_map(|&promo| Move::promotion(src, dest, promo, capture)));
+                } else if capture && self.en_passe.contains(dest) {
+                    moveset.push(Move::en_passe(src, dest));
+                } else if capture {
+                    moveset.push(Move::capture(src, dest));
+                } else {
+                    moveset.push(Move::new(src, dest));
+                }
+            }
         }
 
         moveset
     }
 
-    pub fn king_moves(&self) -> ArrayVec<Move, 10> {
-        let king_bb = self.self_bb() & self.kings;
+    // castling moves are included here, as quiet moves
+    pub fn king_moves(&self, gen_type: GenType) -> ArrayVec<Move, 10> {
+        let Some(src) = (self.self_bb() & self.kings).first() else { return ArrayVec::new(); };
 
-        let king_rshift: i8 = king_bb.leading_zeros() as i8;
+        // the king evades by stepping anywhere, whether that's safe is checked later
+        let targets = if gen_type == GenType::Evasions { !self.self_bb() } else { self.targets(gen_type) };
+        let moves_bb = Bitboard(gen_king(src.x(), src.y())) & targets;
+        let mut moveset = Self::moves_from_bb::<10>(moves_bb, src, self.enemy_bb());
 
-        // king bitboard at position (1, 1)
-        let mut move_board: u64 = 0x705060;
-        // right shift of the kings position in move_board
-        const MOVE_BOARD_RSHIFT: i8 = 49;
+        if gen_type == GenType::Captures {
+            return moveset;
+        }
 
-        move_board = shr(move_board, king_rshift-MOVE_BOARD_RSHIFT);
+        let y = if self.turn == Color::White { 0 } else { 7 };
+        let side = self.turn as usize;
+        let enemy = self.turn.other();
+        let occupied = self.black | self.white;
+        let own_rooks = self.self_bb() & self.rooks;
 
-        let moveset = Self::moves_from_bb::<10>(move_board, right_shift_to_coords(king_rshift as u8));
+        if src != Square::new(4, y) || self.is_attacked_by(src, enemy) {
+            return moveset;
+        }
+
+        // squares that must be empty, the squares the king crosses, and the rook's corner
+        let castles = [
+            (self.short_castle[side], [5, 6].as_slice(), [5, 6].as_slice(), 7),
+            (self.long_castle[side], [1, 2, 3].as_slice(), [3, 2].as_slice(), 0),
+        ];
+
+        for (allowed, between, crossed, rook_x) in castles {
+            if !allowed || !own_rooks.contains(Square::new(rook_x, y)) {
+                continue;
+            }
+            if between.iter().any(|&x| occupied.contains(Square::new(x, y))) {
+                continue;
+            }
+            if crossed.iter().any(|&x| self.is_attacked_by(Square::new(x, y), enemy)) {
+                continue;
+            }
+
+            moveset.push(Move::castle(src, Square::new(crossed[1], y)));
+        }
+
+        moveset
+    }
+
+    /// Look up a long algebraic move like "e2e4" or "e7e8q" among the legal moves,
+    /// so castling, en passe and capture flags come out right
+    pub fn parse_uci_move(&self, text: &str) -> Result<Move, MoveParseError> {
+        if !text.is_ascii() || !(4..=5).contains(&text.len()) {
+            return Err(MoveParseError::Malformed);
+        }
+
+        let src: Square = text[0..2].parse().map_err(|_| MoveParseError::Malformed)?;
+        let dest: Square = text[2..4].parse().map_err(|_| MoveParseError::Malformed)?;
+        let promo = match text.as_bytes().get(4) {
+            None => None,
+            Some(b'q') => Some(PieceType::Queen),
+            Some(b'r') => Some(PieceType::Rook),
+            Some(b'b') => Some(PieceType::Bishop),
+            Some(b'n') => Some(PieceType::Horses),
+            Some(_) => return Err(MoveParseError::Malformed),
+        };
+
+        self.legal_moves()
+            .into_iter()
+            .find(|mv| mv.src() == src && mv.dest() == dest && mv.promo() == promo)
+            .ok_or(MoveParseError::Illegal)
+    }
+
+    /// Standard algebraic notation of a legal move, like "Nbd7", "exd5", "O-O" or "e8=Q+".
+    /// The move is played and taken back to find the check suffix.
+    pub fn move_to_san(&mut self, mv: &Move) -> String {
+        let mut san = if mv.is_castle() {
+            if mv.dest().x() > mv.src().x() { "O-O" } else { "O-O-O" }.to_string()
+        } else {
+            let kind = self.piece_at(mv.src()).expect("no piece on the move's source square").kind;
+            let mut san = String::new();
+
+            if kind == PieceType::Pawn {
+                if mv.is_capture() {
+                    san.push((b'a' + mv.src().x()) as char);
+                }
+            } else {
+                san.push(Piece::new(Color::White, kind).to_char());
+
+                // name the source file if that tells the pieces apart, otherwise the rank, otherwise both
+                let rivals: Bitboard = self.legal_moves()
+                    .into_iter()
+                    .filter(|other| other.dest() == mv.dest() && other.src() != mv.src())
+                    .map(|other| other.src())
+                    .filter(|&src| self.piece_at(src).is_some_and(|piece| piece.kind == kind))
+                    .collect();
+                if !rivals.is_empty() {
+                    let same_file = !(rivals & Bitboard::file(mv.src().x())).is_empty();
+                    let same_rank = !(rivals & Bitboard::rank(mv.src().y())).is_empty();
+                    let name = mv.src().to_string();
+                    if !same_file {
+                        san.push_str(&name[..1]);
+                    } else if !same_rank {
+                        san.push_str(&name[1..]);
+                    } else {
+                        san.push_str(&name);
+                    }
+                }
+            }
+
+            if mv.is_capture() {
+                san.push('x');
+            }
+            san.push_str(&mv.dest().to_string());
+            if let Some(promo) = mv.promo() {
+                san.push('=');
+                san.push(Piece::new(Color::White, promo).to_char());
+            }
+            san
+        };
+
+        self.make_move(mv);
+        if self.is_checkmate() {
+            san.push('#');
+        } else if self.in_check() {
+            san.push('+');
+        }
+        self.unmake_move();
+
+        san
+    }
+
+    /// Look up a move in standard algebraic notation among the legal moves. Check and annotation
+    /// suffixes are ignored, and "0-0" and promotions without the "=" are accepted too.
+    pub fn parse_san(&self, text: &str) -> Result<Move, MoveParseError> {
+        let text = text.trim_end_matches(['+', '#', '!', '?']);
+        if !text.is_ascii() {
+            return Err(MoveParseError::Malformed);
+        }
+
+        let castle = match text {
+            "O-O" | "0-0" => Some(true),
+            "O-O-O" | "0-0-0" => Some(false),
+            _ => None,
+        };
+        if let Some(short) = castle {
+            return self.legal_moves()
+                .into_iter()
+                .find(|mv| mv.is_castle() && (mv.dest().x() > mv.src().x()) == short)
+                .ok_or(MoveParseError::Illegal);
+        }
+
+        let piece_kind = |ch: u8| match ch {
+            b'K' => Some(PieceType::King),
+            b'Q' => Some(PieceType::Queen),
+            b'R' => Some(PieceType::Rook),
+            b'B' => Some(PieceType::Bishop),
+            b'N' => Some(PieceType::Horses),
+            _ => None,
+        };
+
+        let mut rest = text.as_bytes();
+        let kind = match rest.first().and_then(|&ch| piece_kind(ch)) {
+            Some(kind) => {
+                rest = &rest[1..];
+                kind
+            }
+            None => PieceType::Pawn,
+        };
+
+        let mut promo = None;
+        if kind == PieceType::Pawn
+            && let Some((&last, init)) = rest.split_last()
+            && let Some(piece) = piece_kind(last)
+        {
+            promo = Some(piece);
+            rest = init.strip_suffix(b"=").unwrap_or(init);
+        }
+        if promo == Some(PieceType::King) {
+            return Err(MoveParseError::Malformed);
+        }
+
+        if rest.len() < 2 {
+            return Err(MoveParseError::Malformed);
+        }
+        let (rest, dest) = rest.split_at(rest.len() - 2);
+        let dest: Square = std::str::from_utf8(dest).unwrap().parse().map_err(|_| MoveParseError::Malformed)?;
+        let (capture, hint) = match rest.split_last() {
+            Some((b'x', hint)) => (true, hint),
+            _ => (false, rest),
+        };
+
+        // whatever is left names the source file, rank, or both
+        let (file, rank) = match *hint {
+            [] => (None, None),
+            [file @ b'a'..=b'h'] => (Some(file - b'a'), None),
+            [rank @ b'1'..=b'8'] => (None, Some(rank - b'1')),
+            [file @ b'a'..=b'h', rank @ b'1'..=b'8'] => (Some(file - b'a'), Some(rank - b'1')),
+            _ => return Err(MoveParseError::Malformed),
+        };
+
+        let mut matches = self.legal_moves().into_iter().filter(|mv| {
+            !mv.is_castle()
+                && mv.dest() == dest
+                && mv.promo() == promo
+                && mv.is_capture() == capture
+                && file.is_none_or(|x| mv.src().x() == x)
+                && rank.is_none_or(|y| mv.src().y() == y)
+                && self.piece_at(mv.src()).is_some_and(|piece| piece.kind == kind)
+        });
+
+        match (matches.next(), matches.next()) {
+            (Some(mv), None) => Ok(mv),
+            (Some(_), Some(_)) => Err(MoveParseError::Ambiguous),
+            (None, _) => Err(MoveParseError::Illegal),
+        }
+    }
+
+    /// Every legal move for the side to move
+    pub fn legal_moves(&self) -> MoveList {
+        self.generate(GenType::All)
+    }
+
+    /// Legal captures, en passe included, and promotions to a queen. Underpromotions and quiet
+    /// moves are left out, which is all a quiescence search wants to look at.
+    pub fn legal_captures(&self) -> MoveList {
+        self.generate(GenType::Captures)
+    }
+
+    /// Whether a move, say one remembered from another position, can be played here, flags and
+    /// all. Only this one move is looked at, nothing gets generated except for castling.
+    pub fn is_legal(&self, mv: &Move) -> bool {
+        let (src, dest) = (mv.src(), mv.dest());
+        let own = self.self_bb();
+        let enemy = self.enemy_bb();
+        let occupied = self.occupied();
+
+        if !own.contains(src) || own.contains(dest) {
+            return false;
+        }
+        let Some(kind) = self.piece_type(src) else { return false; };
+
+        // castling has conditions of its own, which the king's generation already goes through
+        if kind == PieceType::King && src.x().abs_diff(dest.x()) == 2 {
+            return self.king_moves(GenType::Quiets).iter().any(|other| other.to_bits() == mv.to_bits());
+        }
+
+        let capture = enemy.contains(dest);
+        let mut en_passe = false;
+        let reachable = match kind {
+            PieceType::Pawn => {
+                let white = self.turn == Color::White;
+                let (forward, capture_dirs, start_y) = if white {
+                    (Direction::Up, [Direction::UpLeft, Direction::UpRight], 1)
+                } else {
+                    (Direction::Down, [Direction::DownLeft, Direction::DownRight], 6)
+                };
+
+                let diagonal = capture_dirs.iter().any(|&dir| src.shift(dir) == Some(dest));
+                en_passe = diagonal && self.en_passe.contains(dest);
+                let step = src.shift(forward).filter(|&step| !occupied.contains(step));
+                let double = step.filter(|_| src.y() == start_y).and_then(|step| step.shift(forward));
+                let push = !capture && (step == Some(dest) || double == Some(dest));
+
+                push || (diagonal && (capture || en_passe))
+            }
+            PieceType::Horses => Bitboard(gen_knight(src.x(), src.y())).contains(dest),
+            PieceType::King => Bitboard(gen_king(src.x(), src.y())).contains(dest),
+            PieceType::Bishop => self.diagonal_attacks(src, occupied).contains(dest),
+            PieceType::Rook => self.straight_attacks(src, occupied).contains(dest),
+            PieceType::Queen => (self.straight_attacks(src, occupied) | self.diagonal_attacks(src, occupied)).contains(dest),
+        };
+        if !reachable {
+            return false;
+        }
+
+        // the move as the generator would have flagged it
+        let promotes = kind == PieceType::Pawn && (dest.y() == 0 || dest.y() == 7);
+        let expected = match mv.promo() {
+            Some(promo) if promotes => Move::promotion(src, dest, promo, capture),
+            None if !promotes && en_passe => Some(Move::en_passe(src, dest)),
+            None if !promotes && capture => Some(Move::capture(src, dest)),
+            None if !promotes => Some(Move::new(src, dest)),
+            _ => None,
+        };
+        if expected.is_none_or(|expected| expected.to_bits() != mv.to_bits()) {
+            return false;
+        }
+
+        // play it out on the occupancy and look at the king from there. Whatever gets captured,
+        // the pawn taken en passe included, doesn't attack anything any more
+        let captured = if en_passe { Square::new(dest.x(), src.y()) } else { dest };
+        let occupied_after = (occupied & !src.bb() & !captured.bb()) | dest;
+        let king = if kind == PieceType::King { dest } else { self.king_square() };
+
+        (self.attackers_to(king, occupied_after) & enemy & !captured.bb()).is_empty()
+    }
+
+    /// The legal moves of one kind for the side to move
+    pub fn generate(&self, gen_type: GenType) -> MoveList {
+        let mut moveset = ArrayVec::new();
+
+        let king = self.king_square();
+        let enemy_bb = self.enemy_bb();
+        let occupied = self.occupied();
+
+        let checkers = self.checkers();
+
+        // look through the king's own square so it can't step back along a checking ray.
+        // castling already checks every square the king crosses
+        for mv in self.king_moves(gen_type) {
+            if mv.is_castle() || (self.attackers_to(mv.dest(), occupied ^ king) & enemy_bb).is_empty() {
+                moveset.push(mv);
+            }
+        }
+
+        // in double check only the king can move
+        if checkers.count() > 1 {
+            return moveset;
+        }
+
+        // in single check every other move has to capture the checker or block its ray
+        let evasions = match checkers.first() {
+            None => Bitboard::FULL,
+            Some(checker) => checkers | self.between(king, checker),
+        };
+
+        let pins = self.pins(king);
+
+        let pseudo_moves = self.pawn_moves(gen_type).into_iter()
+            .chain(self.knight_moves(gen_type))
+            .chain(self.bishop_moves(gen_type))
+            .chain(self.rook_moves(gen_type))
+            .chain(self.queen_moves(gen_type));
+
+        for mv in pseudo_moves {
+            let (src, dest) = (mv.src(), mv.dest());
+
+            // en passe removes two pieces from the board at once, which can uncover a slider
+            // along the rank, so just check the king against the position after the capture
+            if mv.is_en_passe() {
+                let captured = Square::new(dest.x(), src.y());
+                let occupied_after = (occupied ^ src ^ captured) | dest;
+                if (self.attackers_to(king, occupied_after) & enemy_bb & !captured.bb()).is_empty() {
+                    moveset.push(mv);
+                }
+                continue;
+            }
+
+            if !evasions.contains(dest) {
+                continue;
+            }
+            if pins.iter().any(|(pinned, line)| pinned.contains(src) && !line.contains(dest)) {
+                continue;
+            }
+
+            moveset.push(mv);
+        }
 
         moveset
     }
+
+    /// Count the leaf nodes of the legal move tree to the given depth
+    pub fn perft(&mut self, depth: u32) -> u64 {
+        if depth == 0 {
+            return 1;
+        }
+
+        let moves = self.legal_moves();
+        // no need to play out the last ply
+        if depth == 1 {
+            return moves.len() as u64;
+        }
+
+        let mut nodes = 0;
+        for mv in &moves {
+            self.make_move(mv);
+            nodes += self.perft(depth - 1);
+            self.unmake_move();
+        }
+
+        nodes
+    }
+
+    /// perft split up by root move, for finding which move a generator bug hides under
+    pub fn perft_divide(&mut self, depth: u32) -> Vec<(Move, u64)> {
+        let mut divided = Vec::new();
+
+        for mv in self.legal_moves() {
+            self.make_move(&mv);
+            let nodes = self.perft(depth.saturating_sub(1));
+            self.unmake_move();
+            divided.push((mv, nodes));
+        }
+
+        divided
+    }
+
+    /// Own pieces pinned to the king, paired with the line each one may still move along
+    fn pins(&self, king: Square) -> ArrayVec<(Bitboard, Bitboard), 8> {
+        let mut pins = ArrayVec::new();
+
+        let enemy_bb = self.enemy_bb();
+        let occupied = self.black | self.white;
+
+        // enemy sliders that would see the king if none of our pieces were in the way
+        let pinners = (self.straight_attacks(king, enemy_bb) & (self.rooks | self.queens)
+            | self.diagonal_attacks(king, enemy_bb) & (self.bishops | self.queens))
+            & enemy_bb;
+
+        for pinner in pinners {
+            let between = self.between(king, pinner);
+            let blockers = between & occupied;
+
+            if blockers.count() == 1 {
+                pins.push((blockers, between | pinner));
+            }
+        }
+
+        pins
+    }
+
+    /// squares strictly between two squares on a shared rank, file or diagonal, or empty if not aligned
+    fn between(&self, a: Square, b: Square) -> Bitboard {
+        // rays from each square stopped by the other only overlap in between them
+        if gen_straight_ray(a.x(), a.y()) & b.bb().0 != 0 {
+            self.straight_attacks(a, b.bb()) & self.straight_attacks(b, a.bb())
+        } else if gen_diagonal_ray(a.x(), a.y()) & b.bb().0 != 0 {
+            self.diagonal_attacks(a, b.bb()) & self.diagonal_attacks(b, a.bb())
+        } else {
+            Bitboard::EMPTY
+        }
+    }
+
+    /// Every piece of either color attacking a square, with sliders blocked by `occupancy`.
+    /// Pass the occupancy with pieces removed to see through them.
+    pub fn attackers_to(&self, square: Square, occupancy: Bitboard) -> Bitboard {
+        let (x, y) = square.coords();
+
+        let mut found = Bitboard(gen_knight(x, y)) & self.knights;
+        found |= Bitboard(gen_king(x, y)) & self.kings;
+
+        // a pawn standing on the square threatens exactly where the other color's attacking pawns are
+        let (_, white_pawns) = gen_pawn_moves(x, y, false);
+        let (_, black_pawns) = gen_pawn_moves(x, y, true);
+        found |= Bitboard(white_pawns) & self.pawns & self.white;
+        found |= Bitboard(black_pawns) & self.pawns & self.black;
+
+        found |= self.straight_attacks(square, occupancy) & (self.rooks | self.queens);
+        found |= self.diagonal_attacks(square, occupancy) & (self.bishops | self.queens);
+
+        found
+    }
+
+    /// Whether any piece of the given color attacks a square in the current position
+    pub fn is_attacked_by(&self, square: Square, color: Color) -> bool {
+        !(self.attackers_to(square, self.occupied()) & self.color_bb(color)).is_empty()
+    }
+
+    /// Enemy pieces giving check to the side to move
+    pub fn checkers(&self) -> Bitboard {
+        self.attackers_to(self.king_square(), self.occupied()) & self.enemy_bb()
+    }
+
+    pub fn in_check(&self) -> bool {
+        !self.checkers().is_empty()
+    }
+
+    fn king_square(&self) -> Square {
+        (self.self_bb() & self.kings).first().expect("side to move has no king")
+    }
+
+    /// How the game has ended, if it has. Mate and stalemate take precedence over the draw rules,
+    /// so a mate delivered on the hundredth halfmove still wins.
+    pub fn result(&self) -> Option<GameResult> {
+        if self.legal_moves().is_empty() {
+            return Some(if self.in_check() {
+                GameResult::Checkmate { winner: self.turn.other() }
+            } else {
+                GameResult::Stalemate
+            });
+        }
+
+        if self.is_threefold() {
+            Some(GameResult::ThreefoldRepetition)
+        } else if self.is_fifty_move_draw() {
+            Some(GameResult::FiftyMoveRule)
+        } else if self.is_insufficient_material() {
+            Some(GameResult::InsufficientMaterial)
+        } else {
+            None
+        }
+    }
+
+    pub fn is_checkmate(&self) -> bool {
+        self.in_check() && self.legal_moves().is_empty()
+    }
+
+    pub fn is_stalemate(&self) -> bool {
+        !self.in_check() && self.legal_moves().is_empty()
+    }
+
+    /// How many times the current position occurred before, going back as far as the last
+    /// capture or pawn move since nothing before that can repeat
+    pub fn repetitions(&self) -> usize {
+        self.repetitions_within(usize::MAX)
+    }
+
+    /// Like repetitions, but only looking back as far as `plies` halfmoves, say to the root of a search
+    pub fn repetitions_within(&self, plies: usize) -> usize {
+        let reversible = (self.halfmove_clock as usize).min(self.history.len()).min(plies);
+
+        // only positions with the same side to move can match, so step back two plies at a time
+        self.history[self.history.len() - reversible..]
+            .iter()
+            .rev()
+            .skip(1)
+            .step_by(2)
+            .filter(|undo| undo.key == self.key)
+            .count()
+    }
+
+    /// The current position has been on the board three times
+    pub fn is_threefold(&self) -> bool {
+        self.repetitions() >= 2
+    }
+
+    /// A hundred halfmoves without a capture or pawn move
+    pub fn is_fifty_move_draw(&self) -> bool {
+        self.halfmove_clock >= 100
+    }
+
+    /// Neither side has enough material left to ever mate: bare kings, a single minor piece,
+    /// or only bishops that all stand on the same color of square
+    pub fn is_insufficient_material(&self) -> bool {
+        if !(self.pawns | self.rooks | self.queens).is_empty() {
+            return false;
+        }
+
+        let minors = self.knights | self.bishops;
+        if minors.count() <= 1 {
+            return true;
+        }
+
+        let mut bishop_colors = self.bishops.into_iter().map(|square| (square.x() + square.y()) % 2);
+        self.knights.is_empty() && bishop_colors.next().is_some_and(|first| bishop_colors.all(|color| color == first))
+    }
 }
 
 #[cfg(test)]
@@ -567,6 +1918,602 @@ mod tests {
         assert!(gamestate.is_err());
     }
 
+    #[test]
+    pub fn import_fen_fields() {
+        let game = GameState::try_from_fen("rnbqkbnr/pp1ppppp/8/2p5/4P3/8/PPPP1PPP/RNBQKBNR w KQkq c6 0 2").unwrap();
+        assert_eq!(game.turn, Color::White);
+        assert_eq!(game.short_castle, [true, true]);
+        assert_eq!(game.long_castle, [true, true]);
+        assert_eq!(game.en_passe, Square::new(2, 5).bb());
+        assert_eq!(game.halfmove_clock, 0);
+        assert_eq!(game.fullmove_number, 2);
+
+        let game = GameState::try_from_fen("r3k2r/8/8/8/3pP3/8/8/R3K2R b Kq e3 3 17").unwrap();
+        assert_eq!(game.turn, Color::Black);
+        assert_eq!(game.short_castle, [false, true]);
+        assert_eq!(game.long_castle, [true, false]);
+        assert_eq!(game.en_passe, Square::new(4, 2).bb());
+        assert_eq!(game.halfmove_clock, 3);
+        assert_eq!(game.fullmove_number, 17);
+
+        // omitted fields fall back to defaults
+        let game = GameState::try_from_fen("8/8/8/4p1K1/2k1P3/8/8/8").unwrap();
+        assert_eq!(game.turn, Color::White);
+        assert_eq!(game.short_castle, [false, false]);
+        assert_eq!(game.long_castle, [false, false]);
+        assert_eq!(game.en_passe, Bitboard::EMPTY);
+        assert_eq!(game.fullmove_number, 1);
+
+        assert!(GameState::try_from_fen("8/8/8/4p1K1/2k1P3/8/8/8 x - - 0 1").is_err());
+        assert!(GameState::try_from_fen("8/8/8/4p1K1/2k1P3/8/8/8 w KX - 0 1").is_err());
+        assert!(GameState::try_from_fen("8/8/8/4p1K1/2k1P3/8/8/8 w - e4 0 1").is_err());
+        assert!(GameState::try_from_fen("8/8/8/4p1K1/2k1P3/8/8/8 w - i6 0 1").is_err());
+        assert!(GameState::try_from_fen("8/8/8/4p1K1/2k1P3/8/8/8 w - - x 1").is_err());
+        assert!(GameState::try_from_fen("8/8/8/4p1K1/2k1P3/8/8/8 w - - 0 1 extra").is_err());
+    }
+
+    #[test]
+    pub fn fen_errors() {
+        assert_eq!(GameState::try_from_fen("").err(), Some(FenError::Empty));
+        assert_eq!(
+            GameState::try_from_fen("rnbqkbnr/pppppppp/8/8/8/PPPPPPPP/RNBQKBNR").err(),
+            Some(FenError::RankCount { found: 7 })
+        );
+        assert_eq!(
+            GameState::try_from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR/8").err(),
+            Some(FenError::RankCount { found: 9 })
+        );
+        assert_eq!(
+            GameState::try_from_fen("rnbqkbnr/pppppppp/9/8/8/8/PPPPPPPP/RNBQKBNR").err(),
+            Some(FenError::RankOverflow { rank: 6, position: 18 })
+        );
+        assert_eq!(
+            GameState::try_from_fen("rnbqknr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR").err(),
+            Some(FenError::RankIncomplete { rank: 8, position: 7 })
+        );
+        assert_eq!(
+            GameState::try_from_fen("rnbqkbnX/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR").err(),
+            Some(FenError::BadPiece { ch: 'X', position: 7 })
+        );
+        assert_eq!(
+            GameState::try_from_fen("rnbqkbnp/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR").err(),
+            Some(FenError::PawnOnBackRank { position: 7 })
+        );
+        assert_eq!(
+            GameState::try_from_fen("8/8/8/8/8/8/1P6/R7").err(),
+            Some(FenError::KingCount { white: true, found: 0 })
+        );
+        assert_eq!(
+            GameState::try_from_fen("k6k/8/8/8/8/8/1P6/K7").err(),
+            Some(FenError::KingCount { white: false, found: 2 })
+        );
+
+        let fen = "8/8/8/4p1K1/2k1P3/8/8/8";
+        assert_eq!(GameState::try_from_fen(&format!("{fen} x - - 0 1")).err(), Some(FenError::BadSideToMove { position: 24 }));
+        assert_eq!(GameState::try_from_fen(&format!("{fen} w KX - 0 1")).err(), Some(FenError::BadCastling { ch: 'X', position: 27 }));
+        assert_eq!(GameState::try_from_fen(&format!("{fen} w - e4 0 1")).err(), Some(FenError::BadEnPassant { position: 28 }));
+        assert_eq!(GameState::try_from_fen(&format!("{fen} b - e6 0 1")).err(), Some(FenError::BadEnPassant { position: 28 }));
+        assert_eq!(GameState::try_from_fen(&format!("{fen} w - - x 1")).err(), Some(FenError::BadClock { position: 30 }));
+        assert_eq!(GameState::try_from_fen(&format!("{fen} w - - 0 1 extra")).err(), Some(FenError::TrailingField { position: 34 }));
+
+        let err = GameState::try_from_fen("rnbqkbnX/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR").err().unwrap();
+        assert_eq!(err.to_string(), "invalid piece character 'X' at position 7");
+    }
+
+    #[test]
+    pub fn set_fen() {
+        let mut game = GameState::new();
+        game.init_magics();
+        game.make_move(&Move::new((4, 1), (4, 3)));
+
+        let fen = "r3k2r/8/8/8/3pP3/8/8/R3K2R b Kq e3 3 17";
+        game.set_fen(fen).unwrap();
+        assert_eq!(game.to_fen(), fen);
+        // the magics carry over and the old move history doesn't
+        assert_eq!(game.legal_moves().len(), 27);
+        assert_eq!(game.repetitions(), 0);
+
+        assert!(game.set_fen("not a fen").is_err());
+        assert_eq!(game.to_fen(), fen);
+    }
+
+    #[test]
+    pub fn legal_captures() {
+        let game = |fen: &str| {
+            let mut game = GameState::try_from_fen(fen).unwrap();
+            game.init_magics();
+            game
+        };
+
+        let kiwipete = game("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1");
+        let captures = kiwipete.legal_captures();
+        assert_eq!(captures.len(), 8);
+        assert!(captures.iter().all(|mv| mv.is_capture()));
+        let expected: Vec<Move> = kiwipete.legal_moves().into_iter().filter(|mv| mv.is_capture()).collect();
+        assert_eq!(captures.to_vec(), expected);
+
+        // only queen promotions, quiet or not, and the en passe capture
+        let promos = game("r3k3/1P6/8/3pP3/8/8/8/4K3 w - d6 0 1");
+        let mut moves: Vec<String> = promos.legal_captures().iter().map(|mv| mv.to_string()).collect();
+        moves.sort();
+        assert_eq!(moves, ["b7a8q", "b7b8q", "e5d6"]);
+
+        // in check, captures that don't deal with it are still illegal
+        let check = game("4k3/8/8/8/8/p4n2/8/1N2KR2 w - - 0 1");
+        let moves: Vec<String> = check.legal_captures().iter().map(|mv| mv.to_string()).collect();
+        assert_eq!(moves, ["f1f3"]);
+    }
+
+    #[test]
+    pub fn gen_types() {
+        // every position two plies into some busy ones, checks included
+        fn check_tree(game: &mut GameState, depth: u32) {
+            let all = game.generate(GenType::All);
+            let captures = game.generate(GenType::Captures);
+            let quiets = game.generate(GenType::Quiets);
+
+            let mut split: Vec<Move> = captures.iter().chain(&quiets).copied().collect();
+            let mut expected = all.to_vec();
+            split.sort_by_key(|mv| format!("{mv:?}"));
+            expected.sort_by_key(|mv| format!("{mv:?}"));
+            assert_eq!(split, expected, "{}", game.to_fen());
+
+            assert!(captures.iter().all(|mv| if mv.is_promo() { mv.promo() == Some(PieceType::Queen) } else { mv.is_capture() }));
+            assert!(quiets.iter().all(|mv| if mv.is_promo() { mv.promo() != Some(PieceType::Queen) } else { !mv.is_capture() }));
+            if game.in_check() {
+                assert_eq!(game.generate(GenType::Evasions), all, "{}", game.to_fen());
+            }
+
+            if depth > 0 {
+                for mv in all {
+                    game.make_move(&mv);
+                    check_tree(game, depth - 1);
+                    game.unmake_move();
+                }
+            }
+        }
+
+        for fen in [
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+            "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+        ] {
+            let mut game = GameState::try_from_fen(fen).unwrap();
+            game.init_magics();
+            check_tree(&mut game, 2);
+        }
+    }
+
+    #[test]
+    pub fn uci_moves() {
+        let mut game = GameState::try_from_fen("r3k2r/1P6/8/3pP3/8/8/8/R3K2R w KQkq d6 0 1").unwrap();
+        game.init_magics();
+
+        let castle = game.parse_uci_move("e1g1").unwrap();
+        assert_eq!(castle, Move::castle((4, 0), (6, 0)));
+        assert_eq!(castle.to_uci(), "e1g1");
+
+        let en_passe = game.parse_uci_move("e5d6").unwrap();
+        assert_eq!(en_passe, Move::en_passe((4, 4), (3, 5)));
+
+        let promo = game.parse_uci_move("b7a8n").unwrap();
+        assert_eq!(promo, Move::promotion((1, 6), (0, 7), PieceType::Horses, true).unwrap());
+        assert_eq!(promo.to_uci(), "b7a8n");
+
+        for illegal in ["e1e3", "b7b8", "e5e6q", "a8a7"] {
+            assert_eq!(game.parse_uci_move(illegal), Err(MoveParseError::Illegal), "{illegal}");
+        }
+        for malformed in ["", "e2", "e2e4qq", "i2i4", "e2e9", "b7b8k", "e2-e4", "é2e4"] {
+            assert_eq!(game.parse_uci_move(malformed), Err(MoveParseError::Malformed), "{malformed}");
+        }
+    }
+
+    #[test]
+    pub fn san_moves() {
+        let game = |fen: &str| {
+            let mut game = GameState::try_from_fen(fen).unwrap();
+            game.init_magics();
+            game
+        };
+        let round_trip = |game: &mut GameState, mv: Move, san: &str| {
+            assert_eq!(game.move_to_san(&mv), san);
+            assert_eq!(game.parse_san(san), Ok(mv), "{san}");
+        };
+
+        let mut start = game("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1");
+        round_trip(&mut start, Move::new((6, 0), (5, 2)), "Nf3");
+        round_trip(&mut start, Move::new((4, 1), (4, 3)), "e4");
+        assert_eq!(start.parse_san("Ng1f3"), Ok(Move::new((6, 0), (5, 2))));
+        assert_eq!(start.parse_san("e5"), Err(MoveParseError::Illegal));
+        assert_eq!(start.parse_san("Nxf3"), Err(MoveParseError::Illegal));
+        assert_eq!(start.parse_san("Zf3"), Err(MoveParseError::Malformed));
+        assert_eq!(start.parse_san("N"), Err(MoveParseError::Malformed));
+
+        // knights on b1 and f3 both reach d2, rooks on a1 and a5 both reach a3
+        let mut knights = game("4k3/8/8/R7/3p4/5N2/8/RN2K3 w - - 0 1");
+        round_trip(&mut knights, Move::new((1, 0), (3, 1)), "Nbd2");
+        round_trip(&mut knights, Move::capture((5, 2), (3, 3)), "Nxd4");
+        round_trip(&mut knights, Move::new((0, 4), (0, 2)), "R5a3");
+        round_trip(&mut knights, Move::new((0, 0), (0, 2)), "R1a3");
+        assert_eq!(knights.parse_san("Nd2"), Err(MoveParseError::Ambiguous));
+        assert_eq!(knights.parse_san("Ra3"), Err(MoveParseError::Ambiguous));
+
+        // the queen on h4 shares a file with one rival and a rank with the other
+        let mut queens = game("2k5/8/8/8/4Q2Q/8/K7/7Q w - - 0 1");
+        round_trip(&mut queens, Move::new((7, 3), (4, 0)), "Qh4e1");
+        round_trip(&mut queens, Move::new((4, 3), (4, 0)), "Qee1");
+
+        let mut castles = game("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1");
+        round_trip(&mut castles, Move::castle((4, 0), (6, 0)), "O-O");
+        round_trip(&mut castles, Move::castle((4, 0), (2, 0)), "O-O-O");
+        assert_eq!(castles.parse_san("0-0-0"), Ok(Move::castle((4, 0), (2, 0))));
+
+        let mut pawns = game("3r3k/4P3/8/3pP3/8/8/8/K7 w - d6 0 1");
+        round_trip(&mut pawns, Move::en_passe((4, 4), (3, 5)), "exd6");
+        round_trip(&mut pawns, Move::promotion((4, 6), (4, 7), PieceType::Queen, false).unwrap(), "e8=Q+");
+        round_trip(&mut pawns, Move::promotion((4, 6), (3, 7), PieceType::Horses, true).unwrap(), "exd8=N");
+        assert_eq!(pawns.parse_san("e8Q"), Ok(Move::promotion((4, 6), (4, 7), PieceType::Queen, false).unwrap()));
+        assert_eq!(pawns.parse_san("e8"), Err(MoveParseError::Illegal));
+        assert_eq!(pawns.parse_san("e8=K"), Err(MoveParseError::Malformed));
+
+        // fool's mate
+        let mut mate = game("rnbqkbnr/pppp1ppp/8/4p3/6P1/5P2/PPPPP2P/RNBQKBNR b KQkq - 0 2");
+        round_trip(&mut mate, Move::new((3, 7), (7, 3)), "Qh4#");
+    }
+
+    #[test]
+    pub fn export_fen() {
+        let startpos = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+        assert_eq!(GameState::new().to_fen(), startpos);
+        assert_eq!(GameState::new().to_string(), startpos);
+
+        let fens = [
+            "rnbqkbnr/pp1ppppp/8/2p5/4P3/8/PPPP1PPP/RNBQKBNR w KQkq c6 0 2",
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+            "r3k2r/8/8/8/3pP3/8/8/R3K2R b Kq e3 3 17",
+            "8/5k2/3p4/1p1Pp2p/pP2Pp1P/P4P1K/8/8 b - - 99 50",
+        ];
+        for fen in fens {
+            assert_eq!(GameState::try_from_fen(fen).unwrap().to_fen(), fen);
+        }
+    }
+
+    #[test]
+    pub fn piece_lookup() {
+        let game = GameState::new();
+        let e1: Square = "e1".parse().unwrap();
+
+        assert_eq!(game.piece_at(e1), Some(Piece::new(Color::White, PieceType::King)));
+        assert_eq!(game.piece_at("d8".parse().unwrap()), Some(Piece::new(Color::Black, PieceType::Queen)));
+        assert_eq!(game.piece_at("g8".parse().unwrap()).map(Piece::to_char), Some('n'));
+        assert_eq!(game.piece_at("e4".parse().unwrap()), None);
+
+        assert_eq!(game.turn(), Color::White);
+        assert_eq!(game.colored_pieces(Color::White, PieceType::King), e1.bb());
+        assert_eq!(game.pieces(PieceType::Pawn), Bitboard::rank(1) | Bitboard::rank(6));
+        assert_eq!(game.color_bb(Color::Black), Bitboard::rank(6) | Bitboard::rank(7));
+        assert_eq!(game.occupied().count(), 32);
+
+        let total: u32 = PieceType::ALL.iter().map(|&kind| game.pieces(kind).count()).sum();
+        assert_eq!(total, 32);
+    }
+
+    #[test]
+    pub fn move_encoding() {
+        assert_eq!(std::mem::size_of::<Move>(), 2);
+
+        // every square survives the round trip
+        for x in 0..8 {
+            for y in 0..8 {
+                let mv = Move::new((x, y), (7 - x, 7 - y));
+                assert_eq!((mv.src().coords(), mv.dest().coords()), ((x, y), (7 - x, 7 - y)));
+            }
+        }
+
+        let mv = Move::promotion((1, 6), (0, 7), PieceType::Horses, true).unwrap();
+        assert_eq!(mv.promo(), Some(PieceType::Horses));
+        assert!(mv.is_capture() && mv.is_promo() && !mv.is_castle());
+        assert_eq!(mv.to_string(), "b7a8n");
+
+        assert!(Move::en_passe((4, 4), (3, 5)).is_capture());
+        assert!(Move::castle((4, 0), (2, 0)).is_castle());
+        assert!(!Move::capture((4, 0), (2, 0)).is_castle());
+        assert_eq!(Move::new((4, 1), (4, 3)).promo(), None);
+
+        // the flags don't make it a different move, the promotion piece does
+        assert_eq!(Move::new((4, 4), (3, 5)), Move::en_passe((4, 4), (3, 5)));
+        assert_eq!(Move::new((4, 0), (6, 0)), Move::castle((4, 0), (6, 0)));
+        assert_eq!(Move::promotion((1, 6), (0, 7), PieceType::Rook, true).unwrap(), Move::promotion((1, 6), (0, 7), PieceType::Rook, false).unwrap());
+        assert_ne!(Move::promotion((1, 6), (0, 7), PieceType::Rook, true).unwrap(), Move::promotion((1, 6), (0, 7), PieceType::Queen, true).unwrap());
+        assert_ne!(Move::new((1, 6), (0, 7)), Move::promotion((1, 6), (0, 7), PieceType::Queen, true).unwrap());
+
+        for promo in [PieceType::Queen, PieceType::Rook, PieceType::Bishop, PieceType::Horses] {
+            assert_eq!(Move::promotion((4, 6), (4, 7), promo, false).unwrap().promo(), Some(promo));
+        }
+    }
+
+    #[test]
+    pub fn promotion_pieces() {
+        assert_eq!(Move::promotion((4, 6), (4, 7), PieceType::King, false), None);
+        assert_eq!(Move::promotion((4, 6), (4, 7), PieceType::Pawn, true), None);
+    }
+
+    #[test]
+    pub fn make_unmake() {
+        let start = GameState::new().to_fen();
+        let mut game = GameState::new();
+
+        // 1. e4 sets the en passe square, 1... Nf6 doesn't
+        game.make_move(&Move::new((4, 1), (4, 3)));
+        assert_eq!(game.to_fen(), "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1");
+        game.make_move(&Move::new((6, 7), (5, 5)));
+        assert_eq!(game.to_fen(), "rnbqkb1r/pppppppp/5n2/8/4P3/8/PPPP1PPP/RNBQKBNR w KQkq - 1 2");
+        // 2. e5 Nxe4 is a capture
+        game.make_move(&Move::new((4, 3), (4, 4)));
+        game.make_move(&Move::new((5, 5), (4, 4)));
+        assert_eq!(game.to_fen(), "rnbqkb1r/pppppppp/8/4n3/8/8/PPPP1PPP/RNBQKBNR w KQkq - 0 3");
+
+        for _ in 0..4 {
+            game.unmake_move();
+        }
+        assert_eq!(game.to_fen(), start);
+
+        // en passe capture removes the pawn beside the capturing pawn
+        let fen = "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3";
+        let mut game = GameState::try_from_fen(fen).unwrap();
+        game.make_move(&Move::new((4, 4), (5, 5)));
+        assert_eq!(game.to_fen(), "rnbqkbnr/ppp1p1pp/5P2/3p4/8/8/PPPP1PPP/RNBQKBNR b KQkq - 0 3");
+        game.unmake_move();
+        assert_eq!(game.to_fen(), fen);
+
+        // capturing promotion
+        let fen = "r3k2r/1P6/8/8/8/8/8/R3K2R w KQkq - 0 1";
+        let mut game = GameState::try_from_fen(fen).unwrap();
+        game.make_move(&Move::promotion((1, 6), (0, 7), PieceType::Horses, true).unwrap());
+        assert_eq!(game.to_fen(), "N3k2r/8/8/8/8/8/8/R3K2R b KQk - 0 1");
+        game.unmake_move();
+        assert_eq!(game.to_fen(), fen);
+
+        // castling moves the rook and drops both rights for that side
+        game.make_move(&Move::castle((4, 0), (6, 0)));
+        assert_eq!(game.to_fen(), "r3k2r/1P6/8/8/8/8/8/R4RK1 b kq - 1 1");
+        game.make_move(&Move::castle((4, 7), (2, 7)));
+        assert_eq!(game.to_fen(), "2kr3r/1P6/8/8/8/8/8/R4RK1 w - - 2 2");
+        game.unmake_move();
+        game.unmake_move();
+        assert_eq!(game.to_fen(), fen);
+
+        // a king going two squares castles even without the flag
+        game.make_move(&Move::new((4, 0), (6, 0)));
+        assert_eq!(game.to_fen(), "r3k2r/1P6/8/8/8/8/8/R4RK1 b kq - 1 1");
+        game.unmake_move();
+        assert_eq!(game.to_fen(), fen);
+
+        // moving a rook off its corner only drops that side's right
+        game.make_move(&Move::new((7, 0), (7, 7)));
+        assert_eq!(game.to_fen(), "r3k2R/1P6/8/8/8/8/8/R3K3 b Qq - 0 1");
+        game.unmake_move();
+        assert_eq!(game.to_fen(), fen);
+
+        // clocks already at their limit stay there, and come back exactly on unmake
+        let fen = "4k3/8/8/8/8/8/8/R3K3 b - - 65535 65535";
+        let mut game = GameState::try_from_fen(fen).unwrap();
+        game.make_move(&Move::new((4, 7), (3, 7)));
+        assert_eq!(game.to_fen(), "3k4/8/8/8/8/8/8/R3K3 w - - 65535 65535");
+        game.unmake_move();
+        assert_eq!(game.to_fen(), fen);
+    }
+
+    #[test]
+    pub fn zobrist_keys() {
+        // walk every line a few plies deep, checking the incremental key against a recompute
+        fn walk(game: &mut GameState, depth: u32) {
+            let key = game.key();
+            assert_eq!(key, game.compute_key());
+            if depth == 0 {
+                return;
+            }
+
+            for mv in game.legal_moves() {
+                game.make_move(&mv);
+                assert_eq!(game.key(), game.compute_key(), "after {mv}");
+                assert_eq!(game.pawn_key(), game.compute_pawn_key(), "after {mv}");
+                walk(game, depth - 1);
+                game.unmake_move();
+                assert_eq!(game.key(), key);
+            }
+        }
+
+        // castling, promotions and en passe all get exercised between these
+        for fen in [
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+        ] {
+            let mut game = GameState::try_from_fen(fen).unwrap();
+            game.init_magics();
+            walk(&mut game, 3);
+        }
+
+        // the same position reached by different move orders, ignoring the clocks
+        let mut game = GameState::new();
+        game.init_magics();
+        let start = game.key();
+        for mv in [((6, 0), (5, 2)), ((6, 7), (5, 5)), ((5, 2), (6, 0)), ((5, 5), (6, 7))] {
+            game.make_move(&Move::new(mv.0, mv.1));
+        }
+        assert_eq!(game.key(), start);
+
+        // only pawns moving, getting taken or promoting touch the pawn key
+        let pawn_key = game.pawn_key();
+        game.make_move(&Move::new((6, 0), (5, 2)));
+        assert_eq!(game.pawn_key(), pawn_key);
+        game.make_move(&Move::new((4, 6), (4, 4)));
+        assert_ne!(game.pawn_key(), pawn_key);
+        game.unmake_move();
+        game.unmake_move();
+
+        // side to move, castling rights and en passe all change the key
+        let key = |fen: &str| GameState::try_from_fen(fen).unwrap().key();
+        let fen = "r3k2r/8/8/8/3pP3/8/8/R3K2R b KQkq e3 0 1";
+        assert_ne!(key(fen), key("r3k2r/8/8/8/3pP3/8/8/R3K2R w KQkq - 0 1"));
+        assert_ne!(key(fen), key("r3k2r/8/8/8/3pP3/8/8/R3K2R b KQk e3 0 1"));
+        assert_ne!(key(fen), key("r3k2r/8/8/8/3pP3/8/8/R3K2R b KQkq - 0 1"));
+        // unless nothing can take en passe
+        assert_eq!(
+            key("r3k2r/8/8/8/4P3/8/8/R3K2R b KQkq e3 0 1"),
+            key("r3k2r/8/8/8/4P3/8/8/R3K2R b KQkq - 0 1")
+        );
+    }
+
+    #[test]
+    pub fn attack_queries() {
+        let sq = |name: &str| name.parse::<Square>().unwrap();
+
+        // e4 is hit by the knight on f6, the pawn on d5, the rook down the e file and the white pawn on f3
+        let mut game = GameState::try_from_fen("4r2k/8/5n2/3p4/4P3/5P2/8/K7 w - - 0 1").unwrap();
+        game.init_magics();
+        let attackers = game.attackers_to(sq("e4"), game.occupied());
+        assert_eq!(attackers, sq("f6").bb() | sq("d5") | sq("e8") | sq("f3"));
+        // the rook only reaches e3 once the e4 pawn is out of the way
+        assert!(!game.attackers_to(sq("e3"), game.occupied()).contains(sq("e8")));
+        assert!(game.attackers_to(sq("e3"), game.occupied() ^ sq("e4")).contains(sq("e8")));
+
+        assert!(game.is_attacked_by(sq("e4"), Color::Black));
+        assert!(game.is_attacked_by(sq("e4"), Color::White));
+        assert!(!game.is_attacked_by(sq("e3"), Color::Black));
+        assert!(game.is_attacked_by(sq("b2"), Color::White));
+        assert!(!game.in_check());
+        assert!(game.checkers().is_empty());
+
+        // double check from the knight and the rook
+        let mut game = GameState::try_from_fen("4r2k/8/8/8/8/3n4/8/R3K3 w - - 0 1").unwrap();
+        game.init_magics();
+        assert!(game.in_check());
+        assert_eq!(game.checkers(), sq("d3").bb() | sq("e8"));
+
+        // pawns only attack diagonally forwards
+        let mut game = GameState::try_from_fen("7k/8/8/3p4/4K3/8/8/8 w - - 0 1").unwrap();
+        game.init_magics();
+        assert_eq!(game.checkers(), sq("d5").bb());
+        let mut game = GameState::try_from_fen("7k/8/8/8/4K3/3p4/8/8 w - - 0 1").unwrap();
+        game.init_magics();
+        assert!(!game.in_check());
+    }
+
+    #[test]
+    pub fn game_results() {
+        let game = |fen: &str| {
+            let mut game = GameState::try_from_fen(fen).unwrap();
+            game.init_magics();
+            game
+        };
+
+        // fool's mate
+        let mated = game("rnb1kbnr/pppp1ppp/8/4p3/6Pq/5P2/PPPPP2P/RNBQKBNR w KQkq - 1 3");
+        assert!(mated.is_checkmate() && !mated.is_stalemate());
+        assert_eq!(mated.result(), Some(GameResult::Checkmate { winner: Color::Black }));
+        assert_eq!(mated.result().unwrap().winner(), Some(Color::Black));
+
+        let stalemate = game("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1");
+        assert!(stalemate.is_stalemate() && !stalemate.is_checkmate());
+        assert_eq!(stalemate.result(), Some(GameResult::Stalemate));
+        assert!(stalemate.result().unwrap().is_draw());
+
+        assert_eq!(game("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1").result(), None);
+
+        // fifty moves, unless the last of them mates
+        let fifty = game("8/8/8/4k3/8/8/R7/4K3 w - - 100 80");
+        assert!(fifty.is_fifty_move_draw());
+        assert_eq!(fifty.result(), Some(GameResult::FiftyMoveRule));
+        assert!(!game("8/8/8/4k3/8/8/R7/4K3 w - - 99 80").is_fifty_move_draw());
+        let mate = game("R5k1/5ppp/8/8/8/8/8/6K1 b - - 100 80");
+        assert_eq!(mate.result(), Some(GameResult::Checkmate { winner: Color::White }));
+
+        for fen in [
+            "8/8/8/4k3/8/8/8/4K3 w - - 0 1",
+            "8/8/8/4k3/8/8/8/4KN2 w - - 0 1",
+            "8/8/8/4k3/8/8/8/4KB2 w - - 0 1",
+            // bishops on the same color of square, even on opposite sides
+            "8/8/8/4k3/8/3b4/8/4KB2 w - - 0 1",
+            "8/8/8/4k3/8/8/8/3BKB2 w - - 0 1",
+        ] {
+            assert!(game(fen).is_insufficient_material(), "{fen}");
+            assert_eq!(game(fen).result(), Some(GameResult::InsufficientMaterial), "{fen}");
+        }
+        for fen in [
+            "8/8/8/4k3/8/8/4P3/4K3 w - - 0 1",
+            "8/8/8/4k3/8/8/8/4KR2 w - - 0 1",
+            "8/8/8/4k3/8/8/8/3NKB2 w - - 0 1",
+            "8/8/8/4k3/8/8/8/3NKN2 w - - 0 1",
+            "8/8/8/4k3/8/8/8/2B1KB2 w - - 0 1",
+            "8/8/8/4k3/8/2b5/8/4KB2 w - - 0 1",
+        ] {
+            assert!(!game(fen).is_insufficient_material(), "{fen}");
+        }
+
+        // shuffling the knights out and back twice repeats the start position three times
+        let mut repeated = game("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1");
+        let shuffle = [((6, 0), (5, 2)), ((6, 7), (5, 5)), ((5, 2), (6, 0)), ((5, 5), (6, 7))];
+        for (i, (src, dest)) in shuffle.iter().chain(shuffle.iter()).enumerate() {
+            assert!(!repeated.is_threefold(), "after {i} moves");
+            repeated.make_move(&Move::new(*src, *dest));
+        }
+        assert_eq!(repeated.repetitions(), 2);
+        assert!(repeated.is_threefold());
+        assert_eq!(repeated.result(), Some(GameResult::ThreefoldRepetition));
+        repeated.unmake_move();
+        assert!(!repeated.is_threefold());
+
+        // a pawn move in between means nothing before it can repeat
+        let mut reset = game("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1");
+        for (src, dest) in shuffle {
+            reset.make_move(&Move::new(src, dest));
+        }
+        reset.make_move(&Move::new((0, 1), (0, 2)));
+        reset.make_move(&Move::new((0, 6), (0, 5)));
+        for (src, dest) in shuffle {
+            reset.make_move(&Move::new(src, dest));
+        }
+        assert_eq!(reset.repetitions(), 1);
+        // the earlier occurrence is four plies back
+        assert_eq!(reset.repetitions_within(3), 0);
+        assert_eq!(reset.repetitions_within(4), 1);
+    }
+
+    #[test]
+    pub fn generated_flags() {
+        let flagged = |game: &GameState, flag: fn(&Move) -> bool| {
+            let mut moves: Vec<String> = game.generate(GenType::All).iter().filter(|mv| flag(mv)).map(|mv| mv.to_uci()).collect();
+            moves.sort();
+            moves
+        };
+
+        // only moves landing on enemy pieces are captures
+        let mut game = GameState::try_from_fen("2r2r2/pk4pp/1p6/P1p1B3/8/2R2n2/2P2P1P/1R3K2 w - - 0 1").unwrap();
+        game.init_magics();
+        assert_eq!(flagged(&game, Move::is_capture), ["a5b6", "b1b6", "c3c5", "c3f3", "e5g7"]);
+
+        // en passe is a capture, and the only en passe
+        let mut game = GameState::try_from_fen("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1").unwrap();
+        game.init_magics();
+        assert_eq!(flagged(&game, Move::is_en_passe), ["e5d6"]);
+        assert_eq!(flagged(&game, Move::is_capture), ["e5d6"]);
+
+        // promotions onto the knight capture, the pushes don't
+        let mut game = GameState::try_from_fen("1n2k3/P7/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+        game.init_magics();
+        assert_eq!(flagged(&game, Move::is_promo).len(), 8);
+        assert_eq!(flagged(&game, Move::is_capture), ["a7b8b", "a7b8n", "a7b8q", "a7b8r"]);
+
+        // castling is flagged on the king's move
+        let mut game = GameState::try_from_fen("4k3/8/8/8/8/8/8/R3K2R w KQ - 0 1").unwrap();
+        game.init_magics();
+        assert_eq!(flagged(&game, Move::is_castle), ["e1c1", "e1g1"]);
+    }
+
     #[test]
     pub fn rook_moves() {
         // starting board except with a rook at e5 (4, 4) - FEN row 3 = rank 5 = y=4
@@ -589,7 +2536,7 @@ mod tests {
         ];
         expected.sort();
 
-        let mut moves: Vec<Move> = game.rook_moves().to_vec();
+        let mut moves: Vec<Move> = game.rook_moves(GenType::All).to_vec();
         moves.sort();
 
         assert_eq!(expected, moves);
@@ -622,13 +2569,13 @@ mod tests {
         ];
         expected.sort();
 
-        let mut moves: Vec<Move> = game.rook_moves().to_vec();
+        let mut moves: Vec<Move> = game.rook_moves(GenType::All).to_vec();
         moves.sort();
 
         assert_eq!(moves, expected);
 
         // rook at a1 (0, 0) with pawn at b2 (1, 1)
-        let mut game = GameState::try_from_fen("8/8/8/8/8/8/1P6/R7").unwrap();
+        let mut game = GameState::try_from_fen("7k/8/8/8/8/8/1P5K/R7").unwrap();
         game.init_magics();
 
         let mut expected = vec![
@@ -649,7 +2596,7 @@ mod tests {
         ];
         expected.sort();
 
-        let mut moves: Vec<Move> = game.rook_moves().to_vec();
+        let mut moves: Vec<Move> = game.rook_moves(GenType::All).to_vec();
         moves.sort();
 
         assert_eq!(moves, expected);
@@ -684,12 +2631,144 @@ mod tests {
         ];
         expected.sort();
 
-        let mut moves: Vec<Move> = game.bishop_moves().to_vec();
+        let mut moves: Vec<Move> = game.bishop_moves(GenType::All).to_vec();
         moves.sort();
 
         assert_eq!(moves, expected);
     }
 
+    #[test]
+    pub fn pawn_moves() {
+        // c2, e2 and h3 are blocked, d4 can push or take on e5
+        let game = GameState::try_from_fen("4k3/8/8/4p3/3P3p/2N1p2P/2P1P3/4K3 w - - 0 1").unwrap();
+
+        let mut expected = vec![
+            Move::new((3, 3), (3, 4)), // d5
+            Move::new((3, 3), (4, 4)), // dxe5
+        ];
+        expected.sort();
+
+        let mut moves: Vec<Move> = game.pawn_moves(GenType::All).to_vec();
+        moves.sort();
+
+        assert_eq!(moves, expected);
+
+        // double push from the start row, black to move
+        let game = GameState::try_from_fen("4k3/3p4/8/8/8/8/8/4K3 b - - 0 1").unwrap();
+        let mut expected = vec![
+            Move::new((3, 6), (3, 5)),
+            Move::new((3, 6), (3, 4)),
+        ];
+        expected.sort();
+        let mut moves: Vec<Move> = game.pawn_moves(GenType::All).to_vec();
+        moves.sort();
+        assert_eq!(moves, expected);
+
+        // the double push is blocked if the square in front is occupied
+        let game = GameState::try_from_fen("4k3/3p4/3n4/8/8/8/8/4K3 b - - 0 1").unwrap();
+        assert!(game.pawn_moves(GenType::All).is_empty());
+
+        // en passe capture
+        let game = GameState::try_from_fen("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1").unwrap();
+        let mut expected = vec![
+            Move::new((4, 4), (4, 5)),
+            Move::new((4, 4), (3, 5)),
+        ];
+        expected.sort();
+        let mut moves: Vec<Move> = game.pawn_moves(GenType::All).to_vec();
+        moves.sort();
+        assert_eq!(moves, expected);
+
+        // every promotion choice for both the push and the capture
+        let game = GameState::try_from_fen("1n2k3/P7/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+        let mut expected = Vec::new();
+        for dest in [(0, 7), (1, 7)] {
+            for promo in [PieceType::Queen, PieceType::Rook, PieceType::Bishop, PieceType::Horses] {
+                expected.push(Move::promotion((0, 6), dest, promo, false).unwrap());
+            }
+        }
+        expected.sort();
+        let mut moves: Vec<Move> = game.pawn_moves(GenType::All).to_vec();
+        moves.sort();
+        assert_eq!(moves, expected);
+    }
+
+    #[test]
+    pub fn queen_moves() {
+        // queen on d4 hemmed in by its own pawns on c3/e3 and enemy pieces on b6, d7, g4 and g7
+        let mut game = GameState::try_from_fen("4k3/3r2p1/1p6/8/3Q2n1/2P1P3/8/4K3 w - - 0 1").unwrap();
+        game.init_magics();
+
+        let mut expected = vec![
+            // file
+            Move::new((3, 3), (3, 4)),
+            Move::new((3, 3), (3, 5)),
+            Move::new((3, 3), (3, 6)), // capture
+            Move::new((3, 3), (3, 2)),
+            Move::new((3, 3), (3, 1)),
+            Move::new((3, 3), (3, 0)),
+            // rank
+            Move::new((3, 3), (0, 3)),
+            Move::new((3, 3), (1, 3)),
+            Move::new((3, 3), (2, 3)),
+            Move::new((3, 3), (4, 3)),
+            Move::new((3, 3), (5, 3)),
+            Move::new((3, 3), (6, 3)), // capture
+            // diagonals
+            Move::new((3, 3), (2, 4)),
+            Move::new((3, 3), (1, 5)), // capture
+            Move::new((3, 3), (4, 4)),
+            Move::new((3, 3), (5, 5)),
+            Move::new((3, 3), (6, 6)), // capture
+        ];
+        expected.sort();
+
+        let mut moves: Vec<Move> = game.queen_moves(GenType::All).to_vec();
+        moves.sort();
+
+        assert_eq!(moves, expected);
+
+        // every queen gets its moves, not just the first
+        let mut game = GameState::try_from_fen("k7/8/8/8/8/8/1K6/QQQ5 w - - 0 1").unwrap();
+        game.init_magics();
+        let moves = game.queen_moves(GenType::All);
+        for src in [(0, 0), (1, 0), (2, 0)] {
+            assert!(moves.iter().any(|mv| mv.src().coords() == src));
+        }
+        // a1 up the file, b1 along its free diagonal, c1 up, right and diagonally
+        assert_eq!(moves.len(), 7 + 7 + (7 + 5 + 5));
+    }
+
+    #[test]
+    pub fn promoted_pieces() {
+        // eight knights and three each of rooks and bishops all get their moves
+        let mut game = GameState::try_from_fen("k7/8/8/8/1N1N1N1N/N1N1N1N1/4RRR1/BBB2K2 w - - 0 1").unwrap();
+        game.init_magics();
+
+        let knights = game.self_bb() & game.knights;
+        let sources: Vec<(u8, u8)> = game.knight_moves(GenType::All).iter().map(|mv| mv.src().coords()).collect();
+        assert_eq!(knights.count(), 8);
+        for x in 0..8 {
+            let y = if x % 2 == 0 { 2 } else { 3 };
+            assert!(sources.contains(&(x, y)));
+        }
+
+        let rooks: Vec<(u8, u8)> = game.rook_moves(GenType::All).iter().map(|mv| mv.src().coords()).collect();
+        for x in 4..7 {
+            assert!(rooks.contains(&(x, 1)));
+        }
+
+        let bishops: Vec<(u8, u8)> = game.bishop_moves(GenType::All).iter().map(|mv| mv.src().coords()).collect();
+        for x in 0..3 {
+            assert!(bishops.contains(&(x, 0)));
+        }
+
+        // the most moves any legal position has
+        let mut game = GameState::try_from_fen("R6R/3Q4/1Q4Q1/4Q3/2Q4Q/Q4Q2/pp1Q4/kBNN1KB1 w - - 0 1").unwrap();
+        game.init_magics();
+        assert_eq!(game.legal_moves().len(), MAX_MOVES);
+    }
+
     #[test]
     pub fn knight_moves() {
         // position with black knights at f6 (5, 5) and b4 (1, 3)
@@ -713,16 +2792,143 @@ mod tests {
         ];
         expected.sort();
 
-        let mut moves: Vec<Move> = game.knight_moves().to_vec();
+        let mut moves: Vec<Move> = game.knight_moves(GenType::All).to_vec();
         moves.sort();
 
         assert_eq!(moves, expected);
     }
 }
 
+#[test]
+pub fn legal_moves() {
+    fn legal(fen: &str) -> Vec<Move> {
+        let mut game = GameState::try_from_fen(fen).unwrap();
+        game.init_magics();
+        let mut moves = game.legal_moves().to_vec();
+        moves.sort();
+        moves
+    }
+
+    // move counts for the standard test positions from the chessprogramming wiki
+    assert_eq!(legal("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1").len(), 20);
+    assert_eq!(legal("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1").len(), 48);
+    assert_eq!(legal("8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1").len(), 14);
+    assert_eq!(legal("r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1").len(), 6);
+    assert_eq!(legal("rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8").len(), 44);
+    assert_eq!(legal("r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 w - - 0 10").len(), 46);
+
+    // the knight on d2 is pinned by the bishop, the rook on e3 can only slide along the e file
+    let mut expected = vec![
+        Move::new((4, 0), (3, 0)),
+        Move::new((4, 0), (4, 1)),
+        Move::new((4, 0), (5, 0)),
+        Move::new((4, 0), (5, 1)),
+        Move::new((4, 2), (4, 1)),
+        Move::new((4, 2), (4, 3)),
+        Move::new((4, 2), (4, 4)),
+        Move::new((4, 2), (4, 5)),
+        Move::new((4, 2), (4, 6)),
+        Move::new((4, 2), (4, 7)),
+    ];
+    expected.sort();
+    assert_eq!(legal("4r2k/8/8/b7/8/4R3/3N4/4K3 w - - 0 1"), expected);
+
+    // check from the rook on e8: block with the rook on e4 or the bishop on e2, take it, or step aside
+    let mut expected = vec![
+        Move::new((4, 0), (3, 0)),
+        Move::new((4, 0), (5, 0)),
+        Move::new((4, 0), (3, 1)),
+        Move::new((4, 0), (5, 1)),
+        Move::new((0, 3), (4, 3)),
+        Move::new((1, 4), (4, 1)),
+        Move::new((1, 4), (4, 7)),
+    ];
+    expected.sort();
+    assert_eq!(legal("4r2k/8/8/1B6/R7/8/8/4K3 w - - 0 1"), expected);
+
+    // double check from the knight and the rook means only king moves
+    let moves = legal("4r2k/8/8/8/8/3n4/8/R3K3 w - - 0 1");
+    assert!(moves.iter().all(|mv| mv.src() == "e1".parse().unwrap()));
+    assert_eq!(moves.len(), 3);
+
+    // taking en passe would leave both pawns off the fifth rank and expose the king to the rook
+    let moves = legal("8/8/3p4/KPp4r/1R3p1k/8/4P1P1/8 w - c6 0 2");
+    assert!(!moves.contains(&Move::new((1, 4), (2, 5))));
+
+    // but en passe can capture a checking pawn
+    let moves = legal("8/8/8/2k5/3Pp3/8/8/4K3 b - d3 0 1");
+    assert!(moves.contains(&Move::new((4, 3), (3, 2))));
+}
+
+#[test]
+pub fn is_legal() {
+    // every bit pattern a stored move could have, against what the generator comes up with
+    let fens = [
+        "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+        "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+        "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 b kq - 0 1",
+        // en passe that would uncover the rook on the fifth rank
+        "8/8/8/KPp4r/8/8/8/7k w - c6 0 2",
+        // en passe out of check, the pawn that just moved is the checker
+        "8/8/8/2k5/3Pp3/8/8/4K3 b - d3 0 1",
+        // double check
+        "4k3/8/8/8/8/5n2/8/r3K2R w K - 0 1",
+    ];
+
+    for fen in fens {
+        let mut game = GameState::try_from_fen(fen).unwrap();
+        game.init_magics();
+        let legal = game.legal_moves();
+
+        for bits in 0..=u16::MAX {
+            let mv = Move::from_bits(bits);
+            let generated = legal.iter().any(|other| other.to_bits() == bits);
+            assert_eq!(game.is_legal(&mv), generated, "{mv:?} in {fen}");
+        }
+    }
+}
+
+#[test]
+pub fn castling() {
+    fn castles(fen: &str) -> Vec<Move> {
+        let mut game = GameState::try_from_fen(fen).unwrap();
+        game.init_magics();
+        let mut moves: Vec<Move> = game.king_moves(GenType::All).into_iter().filter(|mv| mv.is_castle()).collect();
+        moves.sort();
+        moves
+    }
+    let castle = Move::castle;
+
+    let mut expected = vec![castle((4, 0), (6, 0)), castle((4, 0), (2, 0))];
+    expected.sort();
+    assert_eq!(castles("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1"), expected);
+
+    let mut expected = vec![castle((4, 7), (6, 7)), castle((4, 7), (2, 7))];
+    expected.sort();
+    assert_eq!(castles("r3k2r/8/8/8/8/8/8/R3K2R b KQkq - 0 1"), expected);
+
+    // rights are tracked per color
+    assert_eq!(castles("r3k2r/8/8/8/8/8/8/R3K2R w Qk - 0 1"), vec![castle((4, 0), (2, 0))]);
+    assert_eq!(castles("r3k2r/8/8/8/8/8/8/R3K2R b Qk - 0 1"), vec![castle((4, 7), (6, 7))]);
+
+    // pieces in the way, even on b1 which the king never crosses
+    assert_eq!(castles("r3k2r/8/8/8/8/8/8/RN2K1NR w KQkq - 0 1"), vec![]);
+
+    // can't castle out of check
+    assert_eq!(castles("r3k2r/8/8/8/8/8/4q3/R3K2R w KQkq - 0 1"), vec![]);
+
+    // can't pass through or land on an attacked square
+    assert_eq!(castles("r3k2r/8/8/8/8/8/5r2/R3K2R w KQ - 0 1"), vec![castle((4, 0), (2, 0))]);
+    assert_eq!(castles("r3k2r/8/8/8/8/1n6/8/R3K2R w KQ - 0 1"), vec![castle((4, 0), (6, 0))]);
+
+    // but an attacked b1 only matters for the rook
+    assert_eq!(castles("r3k2r/8/8/8/8/8/8/Rr2K2R w Q - 0 1"), vec![]);
+    assert_eq!(castles("r3k2r/8/8/8/8/1r6/8/R3K2R w Q - 0 1"), vec![castle((4, 0), (2, 0))]);
+}
+
 #[test]
 pub fn king_moves() {
-    let mut game = GameState::try_from_fen("rnbqkbnr/pppppppp/8/3K4/8/8/PPPPPPPP/RNBQ1BNR").unwrap();
+    let mut game = GameState::try_from_fen("rnbqkbnr/pppppppp/8/4K3/8/8/PPPPPPPP/RNBQ1BNR").unwrap();
     game.init_magics();
     game.turn = Color::White;
 
@@ -738,7 +2944,7 @@ pub fn king_moves() {
     ];
     expected.sort();
 
-    let mut moves: Vec<Move> = game.king_moves().to_vec();
+    let mut moves: Vec<Move> = game.king_moves(GenType::All).to_vec();
     moves.sort();
 
     assert_eq!(moves, expected);
@@ -1,5 +1,13 @@
+pub mod bitboard;
+pub mod eval;
 pub mod game;
 // wizardry
 pub mod magic;
 pub mod movegen;
+pub mod movepick;
+pub mod pawns;
+pub mod search;
 pub mod tests;
+pub mod tt;
+pub mod uci;
+pub mod zobrist;
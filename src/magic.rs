@@ -97,7 +97,7 @@ impl MagicTable {
         assert!(blocker_map.len() <= max_len);
 
         let mut rng = StdRng::seed_from_u64(0);
-        let mut magic: u64 = 0;
+        let mut magic: u64;
 
         loop {
             let mut found_magic = true;
@@ -118,8 +118,7 @@ impl MagicTable {
                 let map_index = MagicTable::gen_table_idx(blocker_board, magic, index_bits);
 
                 let blocked_ray = if straight {
-                    let blocked_straight = gen_blocked_straight(x, y, blocker_board);
-                    blocked_straight
+                    gen_blocked_straight(x, y, blocker_board)
                 } else {
                     gen_blocked_diagonal(x, y, blocker_board)
                 };
@@ -150,9 +149,9 @@ impl MagicTable {
 
         Self {
             table: blocker_map,
-            magic: magic,
-            clipped_ray: clipped_ray,
-            index_bits: index_bits,
+            magic,
+            clipped_ray,
+            index_bits,
         }
     }
 }
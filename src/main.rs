@@ -1,44 +1,51 @@
-use arrayvec::ArrayVec;
-use knightmare::{chessboard, game::GameState, magic::*, movegen::*};
-use rand::Rng;
+use knightmare::{game::GameState, magic::*};
+use knightmare::uci::Uci;
+use std::time::Instant;
 
 fn main() {
-    println!("Horsie v{}", env!("CARGO_PKG_VERSION"));
-    println!("By Rift");
-    if let Ok(art) = std::fs::read_to_string("assets/art.txt") {
-        println!("{art}");
+    let args: Vec<String> = std::env::args().collect();
+    if args.get(1).map(String::as_str) == Some("perft") {
+        perft(&args[2..]);
+        return;
     }
 
-    let a: u64 = 0b1;
-    let b = a.overflowing_shl(64);
-
-    println!("{:?} {:?}", a, b);
-
-    // for _ in 0..100_000_000 {
-    //     let x = rng.gen_range(0..8);
-    //     let y = rng.gen_range(0..8);
-
-    //     let mut blockers: u64 = u64::MAX;
-    //     for _ in 0..3 {
-    //         let r: u64 = rng.random();
-    //         blockers = blockers & r;
-    //     }
-    //     let span = gen_blocked_diagonal(x, y, blockers);
-    //     let span = gen_blocked_straight(x, y, blockers);
-    // }
-
-    // this looks ugly as fuck
-    // let mut game = GameState::new();
-    // game.init_magics();
+    // stdout belongs to the UCI session, so the banner goes to stderr where no GUI reads it
+    eprintln!("Horsie v{}", env!("CARGO_PKG_VERSION"));
+    eprintln!("By Rift");
 
+    Uci::new().run();
+}
 
-    // generate_magics();
+/// `knightmare perft <depth> [fen]` prints the node count under each root move, then the total
+fn perft(args: &[String]) {
+    let Some(depth) = args.first().and_then(|depth| depth.parse::<u32>().ok()) else {
+        eprintln!("usage: knightmare perft <depth> [fen]");
+        std::process::exit(1);
+    };
 
-    // let rook_moves = game.rook_moves();
-    // println!("{:?}", rook_moves);
+    let mut game = if args.len() > 1 {
+        match GameState::try_from_fen(&args[1..].join(" ")) {
+            Ok(game) => game,
+            Err(err) => {
+                eprintln!("bad FEN: {err}");
+                std::process::exit(1);
+            }
+        }
+    } else {
+        GameState::new()
+    };
+    game.init_magics();
 
+    let start = Instant::now();
+    let mut total = 0;
+    for (mv, nodes) in game.perft_divide(depth) {
+        println!("{mv}: {nodes}");
+        total += nodes;
+    }
 
-    // boo! hello thereeeeee.......
+    println!();
+    println!("Nodes searched: {total}");
+    println!("Time: {:.3}s", start.elapsed().as_secs_f64());
 }
 
 pub fn generate_magics() -> ([[MagicTable; 8]; 8], [[MagicTable; 8]; 8]) {
@@ -1,19 +1,17 @@
-use std::{cmp::{max, min}, thread};
-
-use crate::magic::print_bitboard;
+use std::cmp::min;
 
 // bitboard movegen
 
-const right_down_diag: u64 = 0x8040201008040201;
-const right_up_diag: u64 = 0x102040810204080;
+const RIGHT_DOWN_DIAG: u64 = 0x8040201008040201;
+const RIGHT_UP_DIAG: u64 = 0x102040810204080;
 
 pub const COLUMN_LEFT: u64 = 0x8080808080808080;
 pub const COLUMN_RIGHT: u64 = 0x8080808080808080 >> 7;
 pub const ROW_TOP: u64 = 0xFF00000000000000;
 pub const ROW_BOTTOM: u64 = 0x00000000000000FF;
 
-const vertical_zeros_right: u64 = 0xFEFEFEFEFEFEFEFE;
-const vertical_zeros_left: u64 = 0x7F7F7F7F7F7F7F7F;
+const VERTICAL_ZEROS_RIGHT: u64 = 0xFEFEFEFEFEFEFEFE;
+const VERTICAL_ZEROS_LEFT: u64 = 0x7F7F7F7F7F7F7F7F;
 
 pub fn shr(val: u64, dist: i8) -> u64 {
     if dist >= 0 {
@@ -29,30 +27,27 @@ fn get_diagonal_rays(x: u8, y: u8) -> (u64, u64) {
     let x = x as i8;
     let y = y as i8;
 
-    let mut right_down: u64 = 0;
-    let mut right_up: u64 = 0;
-
-    right_down = shr(right_down_diag, x + y - 7);
+    let mut right_down = shr(RIGHT_DOWN_DIAG, x + y - 7);
 
     // clip residue bits
     for i in 0..(x + y - 7).abs() {
         let shift_amt = if x + y - 7 >= 0 { i } else { -i };
         let mask = if x + y - 7 >= 0 {
-            vertical_zeros_left
+            VERTICAL_ZEROS_LEFT
         } else {
-            vertical_zeros_right
+            VERTICAL_ZEROS_RIGHT
         };
         right_down &= shr(mask, shift_amt);
     }
 
-    right_up = shr(right_up_diag, x - y);
+    let mut right_up = shr(RIGHT_UP_DIAG, x - y);
 
     for i in 0..(x - y).abs() {
         let shift_amt = if x - y <= 0 { -i } else { i };
         let mask = if x - y <= 0 {
-            vertical_zeros_right
+            VERTICAL_ZEROS_RIGHT
         } else {
-            vertical_zeros_left
+            VERTICAL_ZEROS_LEFT
         };
         right_up &= shr(mask, shift_amt);
     }
@@ -74,7 +69,7 @@ pub fn gen_blocked_diagonal(x: u8, y: u8, other_pieces: u64) -> u64 {
     let top_area = if y == 7 { 0 } else { u64::MAX << ((y + 1) * 8) };
     let bottom_area = !top_area;
     // pretty sure this will work
-    let right_area = u64::wrapping_mul((1u64 << 8 - x) - 1, COLUMN_LEFT) & !COLUMN_LEFT;
+    let right_area = u64::wrapping_mul((1u64 << (8 - x)) - 1, COLUMN_LEFT) & !COLUMN_LEFT;
     // bottom row is lost during first multiply
     let right_area = right_area | right_area >> 8;
     let left_area = !right_area;
@@ -83,22 +78,22 @@ pub fn gen_blocked_diagonal(x: u8, y: u8, other_pieces: u64) -> u64 {
     let quad1 = right_area & top_area;
     let quad1_blockers: u64 = right_up & quad1 & other_pieces;
     let nearest = quad1_blockers.trailing_zeros();
-    let quad1_diag = right_up & (u64::MAX >> 64 - min(nearest + 1, 64)) & quad1;
+    let quad1_diag = right_up & (u64::MAX >> (64 - min(nearest + 1, 64))) & quad1;
 
     let quad2 = left_area & top_area;
     let quad2_blockers: u64 = right_down & quad2 & other_pieces;
     let nearest = quad2_blockers.trailing_zeros();
-    let quad2_diag = right_down & (u64::MAX >> 64 - min(nearest + 1, 64)) & quad2;
+    let quad2_diag = right_down & (u64::MAX >> (64 - min(nearest + 1, 64))) & quad2;
 
     let quad3 = left_area & bottom_area;
     let quad3_blockers: u64 = right_up & quad3 & other_pieces;
     let nearest = quad3_blockers.leading_zeros();
-    let quad3_diag = right_up & (u64::MAX << 64 - min(nearest + 1, 64)) & quad3;
+    let quad3_diag = right_up & (u64::MAX << (64 - min(nearest + 1, 64))) & quad3;
 
     let quad4 = right_area & bottom_area;
     let quad4_blockers: u64 = right_down & quad4 & other_pieces;
     let nearest = quad4_blockers.leading_zeros();
-    let quad4_diag = right_down & (u64::MAX << 64 - min(nearest + 1, 64)) & quad4;
+    let quad4_diag = right_down & (u64::MAX << (64 - min(nearest + 1, 64))) & quad4;
 
     quad1_diag | quad2_diag | quad3_diag | quad4_diag
 }
@@ -122,78 +117,87 @@ pub fn gen_straight_ray(x: u8, y: u8) -> u64 {
     col | row
 }
 
+/// generate straight bitboard accounting for blockers, inclusive of blockers in ray and exclusve of origin
 pub fn gen_blocked_straight(x: u8, y: u8, other_pieces: u64) -> u64 {
     let (col, row) = gen_straight_rays(x, y);
 
-    let top_area = u64::MAX << y * 8;
+    let top_area = u64::MAX << (y * 8);
     let bottom_area = !top_area;
     let top_area = top_area << 8;
-    let right_area = u64::wrapping_mul((1u64 << 8 - x) - 1, COLUMN_LEFT) & !COLUMN_LEFT;
+    let right_area = u64::wrapping_mul((1u64 << (8 - x)) - 1, COLUMN_LEFT) & !COLUMN_LEFT;
     let right_area = right_area | right_area >> 8;
-    let left_area = !right_area << 1 & vertical_zeros_right;
+    let left_area = (!right_area << 1) & VERTICAL_ZEROS_RIGHT;
 
-    let nearest = (other_pieces & top_area).trailing_zeros();
-    let mut top_ray = (u64::MAX >> 64 - min(nearest + 1, 64)) & col & top_area;
-    if top_ray == 0 {
-        top_ray = col & top_area;
-    }
+    // only blockers on the ray itself matter, and the nearest one is the lowest bit going
+    // up or left and the highest bit going down or right
+    let top = col & top_area;
+    let nearest = (other_pieces & top).trailing_zeros();
+    let top_ray = (u64::MAX >> (64 - min(nearest + 1, 64))) & top;
 
-    let nearest = (other_pieces & bottom_area).trailing_zeros();
-    let mut bottom_ray = (u64::MAX << min(nearest - 1, 64)) & col & bottom_area;
-    if bottom_ray == 0 {
-        bottom_ray = col & bottom_area;
-    }
+    let bottom = col & bottom_area;
+    let nearest = (other_pieces & bottom).leading_zeros();
+    let bottom_ray = (u64::MAX << (64 - min(nearest + 1, 64))) & bottom;
 
-    let nearest = (other_pieces & left_area).trailing_zeros();
-    let mut left_ray = (u64::MAX >> 64 - min(nearest + 1, 64)) & row & left_area;
-    if left_ray == 0 {
-        left_ray = row & left_area;
-    }
+    let left = row & left_area;
+    let nearest = (other_pieces & left).trailing_zeros();
+    let left_ray = (u64::MAX >> (64 - min(nearest + 1, 64))) & left;
 
-    let nearest = (other_pieces & right_area).leading_zeros();
-    let mut right_ray = (u64::MAX << 64 - min(nearest + 1, 64)) & row & right_area;
-    if right_ray == 0 {
-        right_ray = row & right_area;
-    }
+    let right = row & right_area;
+    let nearest = (other_pieces & right).leading_zeros();
+    let right_ray = (u64::MAX << (64 - min(nearest + 1, 64))) & right;
 
     top_ray | bottom_ray | left_ray | right_ray
 }
 
 pub fn gen_knight(x: u8, y: u8) -> u64 {
-    const knight_moves: u64 = 0x5088008850000000;
+    const KNIGHT_MOVES: u64 = 0x5088008850000000;
     let x = x as i8;
     let y = y as i8;
 
-    let mut moves: u64 = shr(knight_moves, (x - 2) + (5 - y) * 8);
+    let mut moves: u64 = shr(KNIGHT_MOVES, (x - 2) + (5 - y) * 8);
     if x < 2 {
-        moves &= vertical_zeros_right << 0;
-        moves &= vertical_zeros_right << 1;
+        moves &= VERTICAL_ZEROS_RIGHT;
+        moves &= VERTICAL_ZEROS_RIGHT << 1;
     } else if x > 5 {
-        moves &= vertical_zeros_left >> 0;
-        moves &= vertical_zeros_left >> 1;
+        moves &= VERTICAL_ZEROS_LEFT;
+        moves &= VERTICAL_ZEROS_LEFT >> 1;
     }
 
     moves
 }
 
+pub fn gen_king(x: u8, y: u8) -> u64 {
+    let king_bb = coords_to_bb(x, y);
+
+    // smear sideways first, clipping bits that wrap onto the next row, then up and down
+    let row = king_bb | ((king_bb << 1) & VERTICAL_ZEROS_RIGHT) | ((king_bb >> 1) & VERTICAL_ZEROS_LEFT);
+    (row | (row << 8) | (row >> 8)) & !king_bb
+}
+
 /// returns (move_board, threat_board)
 pub fn gen_pawn_moves(x: u8, y: u8, white: bool) -> (u64, u64) {
     // let x = x as i8;
     // let y = y as i8;
 
+    // a pawn on its last row has nowhere to go
+    if (white && y == 7) || (!white && y == 0) {
+        return (0, 0);
+    }
+
     let first_move = (y == 1 && white) || (y == 6 && !white);
 
     // threat board of white pawn at position (1, 0)
     let mut threat_board: u64 = 0xA0;
     // move board of white pawn at (0, 0)
-    let mut move_board: u64 = 0x8000 << y*8;
+    let mut move_board: u64 = 0x8000 << (y * 8);
     // double move board of white pawn at (0, 0)
     const DOUBLE_MOVE_BOARD: u64 = 0x8080;
 
     if first_move {
         move_board = DOUBLE_MOVE_BOARD;
-        move_board <<= if white { 8 } else { 56 };
-        threat_board <<= if white { 16 } else { 48 };
+        // black pawns push downwards, so their double move starts two rows below the pawn
+        move_board <<= if white { 16 } else { 32 };
+        threat_board <<= if white { 16 } else { 40 };
         // start x position is 1
     } else {
         if !white {
@@ -205,13 +209,13 @@ pub fn gen_pawn_moves(x: u8, y: u8, white: bool) -> (u64, u64) {
     }
 
     move_board >>= x;
-    threat_board >>= x;
-    threat_board <<= 1;
+    // one signed shift, so captures onto the bottom row aren't shifted off the board first
+    threat_board = shr(threat_board, x as i8 - 1);
 
     if x == 0 {
-        threat_board &= vertical_zeros_right
+        threat_board &= VERTICAL_ZEROS_RIGHT
     } else if x == 7 {
-        threat_board &= vertical_zeros_left
+        threat_board &= VERTICAL_ZEROS_LEFT
     }
 
     (move_board, threat_board)
@@ -220,30 +224,11 @@ pub fn gen_pawn_moves(x: u8, y: u8, white: bool) -> (u64, u64) {
 
 
 
-/// convert a number of tiles - from left to right, bottom to top - to an (x, y) coordinate position
-pub fn right_shift_to_coords(offset: u8) -> (u8, u8) {
-    (offset % 8, 7 - (offset / 8))
-}
-
-// convert coordinates to right shift
-pub fn coords_to_right_shift(x: u8, y: u8) -> u8 {
-    x + (7-y) * 8
-}
-
-pub fn coords_to_left_shift(x: u8, y: u8) -> u8 {
-    y * 8 - x + 7
-}
-
 /// generate a bitboard with a single tile at the given position.
 pub fn coords_to_bb(x: u8, y: u8) -> u64 {
     0b1u64 << (y * 8 + 7 - x)
 }
 
-/// right shift to bitboard cause I ain't typin this shit out every time
-pub fn rs_to_bb(right_shift: u32) -> u64 {
-    0b1000000000000000000000000000000000000000000000000000000000000000u64 >> right_shift
-}
-
 // 00000000
 // 00000000
 // 00000000
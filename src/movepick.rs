@@ -0,0 +1,221 @@
+// hands the search its moves one at a time, best guesses first, generating each batch only
+// once the moves before it failed to cause a cutoff
+
+use arrayvec::ArrayVec;
+
+use crate::eval::piece_value;
+use crate::game::{GameState, GenType, MAX_MOVES, Move, MoveList, PieceType};
+
+/// material a move wins: whatever it captures, plus what a pawn turns into
+pub(crate) fn gain(game: &GameState, mv: &Move) -> i32 {
+    let captured = if mv.is_en_passe() {
+        piece_value(PieceType::Pawn)
+    } else {
+        game.piece_at(mv.dest()).map_or(0, |piece| piece_value(piece.kind))
+    };
+    let promoted = mv.promo().map_or(0, |promo| piece_value(promo) - piece_value(PieceType::Pawn));
+
+    captured + promoted
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Stage {
+    HashMove,
+    GenCaptures,
+    GoodCaptures,
+    GenQuiets,
+    Killers,
+    Quiets,
+    BadCaptures,
+    GenEvasions,
+    Evasions,
+    Done,
+}
+
+/// Yields the legal moves of a position in the order the search should try them:
+/// the hash move, captures that don't obviously lose material, killers, the remaining
+/// quiet moves, then the captures that do. In check it's the hash move and then every evasion.
+pub struct MovePicker {
+    stage: Stage,
+    hash_move: Option<Move>,
+    killers: [Option<Move>; 2],
+    next_killer: usize,
+    // only captures get looked at, for quiescence
+    captures_only: bool,
+
+    moves: MoveList,
+    scores: ArrayVec<i32, MAX_MOVES>,
+    bad_captures: MoveList,
+    index: usize,
+}
+
+impl MovePicker {
+    pub fn new(game: &GameState, hash_move: Option<Move>, killers: [Option<Move>; 2]) -> Self {
+        let mut picker = Self::with_stage(Stage::HashMove, hash_move, killers, false);
+        picker.in_check(game);
+        picker
+    }
+
+    /// Captures and queen promotions only, best first, for a quiescence search. Switches
+    /// to evasions when in check, since standing pat isn't an option then.
+    pub fn captures(game: &GameState) -> Self {
+        let mut picker = Self::with_stage(Stage::GenCaptures, None, [None; 2], true);
+        picker.in_check(game);
+        picker
+    }
+
+    fn with_stage(stage: Stage, hash_move: Option<Move>, killers: [Option<Move>; 2], captures_only: bool) -> Self {
+        MovePicker {
+            stage,
+            hash_move,
+            killers,
+            next_killer: 0,
+            captures_only,
+            moves: MoveList::new(),
+            scores: ArrayVec::new(),
+            bad_captures: MoveList::new(),
+            index: 0,
+        }
+    }
+
+    fn in_check(&mut self, game: &GameState) {
+        if game.in_check() {
+            self.stage = if self.hash_move.is_some() { Stage::HashMove } else { Stage::GenEvasions };
+            self.captures_only = false;
+        }
+    }
+
+    /// The next move to try, or None once they've all been handed out. The position has to be
+    /// the one the picker was made for every time this gets called.
+    pub fn next(&mut self, game: &GameState) -> Option<Move> {
+        loop {
+            match self.stage {
+                Stage::HashMove => {
+                    self.stage = if game.in_check() { Stage::GenEvasions } else { Stage::GenCaptures };
+                    match self.hash_move {
+                        Some(mv) if game.is_legal(&mv) => return Some(mv),
+                        // a move from somewhere else that can't be played here
+                        _ => self.hash_move = None,
+                    }
+                }
+                Stage::GenCaptures => {
+                    self.load(game, GenType::Captures);
+                    self.stage = Stage::GoodCaptures;
+                }
+                Stage::GoodCaptures => {
+                    let Some(mv) = self.pick_best() else {
+                        self.stage = if self.captures_only { Stage::BadCaptures } else { Stage::GenQuiets };
+                        self.index = 0;
+                        continue;
+                    };
+                    if Some(mv) == self.hash_move {
+                        continue;
+                    }
+                    // in quiescence everything goes in one pass, best first
+                    if !self.captures_only && loses_material(game, &mv) {
+                        self.bad_captures.push(mv);
+                        continue;
+                    }
+                    return Some(mv);
+                }
+                // the quiets are generated before the killers, since only a killer among them is legal here
+                Stage::GenQuiets => {
+                    self.load(game, GenType::Quiets);
+                    self.stage = Stage::Killers;
+                }
+                Stage::Killers => {
+                    let Some(&killer) = self.killers.get(self.next_killer) else {
+                        self.stage = Stage::Quiets;
+                        continue;
+                    };
+                    self.next_killer += 1;
+                    if let Some(killer) = killer
+                        && Some(killer) != self.hash_move
+                        && let Some(mv) = self.take(&killer)
+                    {
+                        return Some(mv);
+                    }
+                }
+                Stage::Quiets => {
+                    let Some(mv) = self.moves.get(self.index).copied() else {
+                        self.stage = Stage::BadCaptures;
+                        self.index = 0;
+                        continue;
+                    };
+                    self.index += 1;
+                    if Some(mv) == self.hash_move {
+                        continue;
+                    }
+                    return Some(mv);
+                }
+                Stage::BadCaptures => {
+                    let Some(mv) = self.bad_captures.get(self.index).copied() else {
+                        self.stage = Stage::Done;
+                        continue;
+                    };
+                    self.index += 1;
+                    return Some(mv);
+                }
+                Stage::GenEvasions => {
+                    self.load(game, GenType::Evasions);
+                    self.stage = Stage::Evasions;
+                }
+                Stage::Evasions => {
+                    let Some(mv) = self.pick_best() else {
+                        self.stage = Stage::Done;
+                        continue;
+                    };
+                    if Some(mv) == self.hash_move {
+                        continue;
+                    }
+                    return Some(mv);
+                }
+                Stage::Done => return None,
+            }
+        }
+    }
+
+    fn load(&mut self, game: &GameState, gen_type: GenType) {
+        self.moves = game.generate(gen_type);
+        self.scores = self.moves.iter().map(|mv| score(game, mv)).collect();
+        self.index = 0;
+    }
+
+    /// swap the best scored move left to the front of the unpicked ones and hand it out
+    fn pick_best(&mut self) -> Option<Move> {
+        let remaining = self.scores.get(self.index..)?;
+        let (offset, _) = remaining.iter().enumerate().max_by_key(|&(i, &score)| (score, std::cmp::Reverse(i)))?;
+
+        let best = self.index + offset;
+        self.moves.swap(self.index, best);
+        self.scores.swap(self.index, best);
+        self.index += 1;
+
+        Some(self.moves[self.index - 1])
+    }
+
+    /// take a move out of the quiets still to come, if it's there. What comes back is the
+    /// generated move, flagged for this position rather than the one the killer came from
+    fn take(&mut self, mv: &Move) -> Option<Move> {
+        let position = self.index + self.moves[self.index..].iter().position(|other| other == mv)?;
+        self.scores.remove(position);
+        Some(self.moves.remove(position))
+    }
+}
+
+/// captures score by what they win, and by the least valuable piece doing it among equals.
+/// Quiet moves all score the same
+fn score(game: &GameState, mv: &Move) -> i32 {
+    if !mv.is_capture() && !mv.is_promo() {
+        return 0;
+    }
+
+    let attacker = game.piece_at(mv.src()).map_or(0, |piece| piece_value(piece.kind));
+    gain(game, mv) * 16 - attacker / 100
+}
+
+/// a capture of something cheaper than the capturing piece, onto a defended square
+fn loses_material(game: &GameState, mv: &Move) -> bool {
+    let attacker = game.piece_at(mv.src()).map_or(0, |piece| piece_value(piece.kind));
+    gain(game, mv) < attacker && game.is_attacked_by(mv.dest(), game.turn().other())
+}
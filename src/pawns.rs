@@ -0,0 +1,147 @@
+// pawn structure: doubled, isolated, backward, passed and connected pawns. It only depends on
+// where the pawns are, which rarely changes from one node to the next, so scores are cached
+// by the position's pawn key.
+
+use crate::bitboard::{Bitboard, Direction, Square};
+use crate::game::{Color, GameState, PieceType};
+
+// (midgame, endgame) weights
+const DOUBLED: (i32, i32) = (-10, -25);
+const ISOLATED: (i32, i32) = (-5, -15);
+const BACKWARD: (i32, i32) = (-9, -20);
+// side by side with a friendly pawn
+const PHALANX: (i32, i32) = (7, 5);
+// per pawn defending this one, links in a chain
+const SUPPORTED: (i32, i32) = (8, 6);
+// passed pawns by how far up the board they are, from their own side
+const PASSED_MG: [i32; 8] = [0, 5, 10, 15, 30, 55, 90, 0];
+const PASSED_EG: [i32; 8] = [0, 10, 15, 30, 55, 95, 150, 0];
+
+/// every square on the ranks in front of `y`, from `color`'s side
+fn ranks_ahead(color: Color, y: u8) -> Bitboard {
+    let below = Bitboard((1u64 << (y * 8)) - 1);
+    let above = !(below | Bitboard::rank(y));
+    if color == Color::White { above } else { below }
+}
+
+fn adjacent_files(x: u8) -> Bitboard {
+    let left = if x > 0 { Bitboard::file(x - 1) } else { Bitboard::EMPTY };
+    let right = if x < 7 { Bitboard::file(x + 1) } else { Bitboard::EMPTY };
+    left | right
+}
+
+/// squares a pawn of `color` on `square` attacks
+fn pawn_attacks(color: Color, square: Square) -> Bitboard {
+    let (left, right) = if color == Color::White {
+        (Direction::UpLeft, Direction::UpRight)
+    } else {
+        (Direction::DownLeft, Direction::DownRight)
+    };
+    square.bb().shift(left) | square.bb().shift(right)
+}
+
+/// (midgame, endgame) score of one side's pawns
+fn side_structure(color: Color, own: Bitboard, enemy: Bitboard) -> (i32, i32) {
+    let mut mg = 0;
+    let mut eg = 0;
+    let mut add = |(term_mg, term_eg): (i32, i32), times: i32| {
+        mg += term_mg * times;
+        eg += term_eg * times;
+    };
+
+    let forward = if color == Color::White { Direction::Up } else { Direction::Down };
+
+    for square in own {
+        let (x, y) = square.coords();
+        let ahead = ranks_ahead(color, y);
+        let neighbours = own & adjacent_files(x);
+
+        // a pawn behind a friendly one is the extra pawn on the file
+        let doubled = !(own & Bitboard::file(x) & ahead).is_empty();
+        let isolated = neighbours.is_empty();
+        // pawns of ours defending this one are the ones it would defend if it were theirs
+        let supporters = own & pawn_attacks(color.other(), square);
+        let phalanx = own & (square.bb().shift(Direction::Left) | square.bb().shift(Direction::Right));
+        let passed = !doubled && (enemy & (Bitboard::file(x) | adjacent_files(x)) & ahead).is_empty();
+
+        // nothing beside or behind it can come up to defend it, and stepping up walks into a pawn
+        let stop = square.bb().shift(forward);
+        let backward = !isolated
+            && (neighbours & !ahead).is_empty()
+            && stop.first().is_some_and(|stop| !(enemy & pawn_attacks(color, stop)).is_empty());
+
+        if doubled {
+            add(DOUBLED, 1);
+        }
+        if isolated {
+            add(ISOLATED, 1);
+        } else if backward {
+            add(BACKWARD, 1);
+        }
+        if !phalanx.is_empty() {
+            add(PHALANX, 1);
+        }
+        add(SUPPORTED, supporters.count() as i32);
+        if passed {
+            let rank = if color == Color::White { y } else { 7 - y } as usize;
+            add((PASSED_MG[rank], PASSED_EG[rank]), 1);
+        }
+    }
+
+    (mg, eg)
+}
+
+/// (midgame, endgame) score of the pawn structure, from white's point of view
+pub fn pawn_structure(game: &GameState) -> (i32, i32) {
+    let white = game.colored_pieces(Color::White, PieceType::Pawn);
+    let black = game.colored_pieces(Color::Black, PieceType::Pawn);
+
+    let (white_mg, white_eg) = side_structure(Color::White, white, black);
+    let (black_mg, black_eg) = side_structure(Color::Black, black, white);
+    (white_mg - black_mg, white_eg - black_eg)
+}
+
+#[derive(Clone, Copy, Default)]
+struct PawnEntry {
+    key: u64,
+    mg: i32,
+    eg: i32,
+}
+
+/// Cache of pawn structure scores, one entry per slot, newer results pushing out older ones
+pub struct PawnTable {
+    entries: Vec<PawnEntry>,
+}
+
+impl PawnTable {
+    /// table of 2^bits entries
+    pub fn new(bits: u32) -> Self {
+        PawnTable { entries: vec![PawnEntry::default(); 1 << bits] }
+    }
+
+    /// pawn_structure, looked up by pawn key before it's worked out
+    pub fn probe(&mut self, game: &GameState) -> (i32, i32) {
+        let key = game.pawn_key();
+        let index = (key as usize) & (self.entries.len() - 1);
+
+        let entry = &mut self.entries[index];
+        // a position without pawns has key 0, same as an empty slot, and scores 0 all the same
+        if entry.key != key {
+            let (mg, eg) = pawn_structure(game);
+            *entry = PawnEntry { key, mg, eg };
+        }
+        (entry.mg, entry.eg)
+    }
+
+    /// empty every slot, for a new game
+    pub fn clear(&mut self) {
+        self.entries.fill(PawnEntry::default());
+    }
+}
+
+impl Default for PawnTable {
+    fn default() -> Self {
+        // 16k entries, about 256KB
+        PawnTable::new(14)
+    }
+}
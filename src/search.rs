@@ -0,0 +1,339 @@
+// iterative deepening negamax with alpha-beta pruning, and a quiescence search at the leaves
+
+use arrayvec::ArrayVec;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+use crate::eval::evaluate;
+use crate::game::{GameState, Move};
+use crate::movepick::{MovePicker, gain};
+use crate::pawns::PawnTable;
+use crate::tt::{Bound, TranspositionTable};
+
+/// deepest the search will ever go, in plies from the root
+pub const MAX_PLY: usize = 64;
+
+// a mate found n plies from the root scores MATE - n, so shorter mates score higher
+pub(crate) const MATE: i32 = 32_000;
+const INFINITY: i32 = MATE + 1;
+
+// how much positional gain a capture is allowed on top of the material it wins before
+// quiescence stops believing it can bring the score back up to alpha
+const DELTA_MARGIN: i32 = 200;
+
+/// a line of moves from some position, best first
+pub type Line = ArrayVec<Move, MAX_PLY>;
+
+/// When to give up searching. Anything left as None is unlimited.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Limits {
+    pub depth: Option<u32>,
+    pub nodes: Option<u64>,
+    pub time: Option<Duration>,
+}
+
+/// What a position is worth to the side to move
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Score {
+    Centipawns(i32),
+    /// mate in this many moves, negative when it's the side to move getting mated
+    Mate(i32),
+}
+
+impl Score {
+    fn from_internal(score: i32) -> Self {
+        let plies = MATE - score.abs();
+        if plies as usize > MAX_PLY {
+            return Score::Centipawns(score);
+        }
+
+        let moves = (plies + 1) / 2;
+        Score::Mate(if score > 0 { moves } else { -moves })
+    }
+}
+
+/// Result of the deepest iteration that ran to completion. When a limit cuts the first depth
+/// short, depth is 0 and the move, score and line are the best of the root moves searched by
+/// then, or the first legal move with a score of 0 and no line if not even one got finished.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SearchResult {
+    /// None when the side to move has no legal moves
+    pub best_move: Option<Move>,
+    pub score: Score,
+    pub pv: Line,
+    pub depth: u32,
+    /// nodes visited over every iteration so far
+    pub nodes: u64,
+    pub elapsed: Duration,
+}
+
+struct Searcher<'a> {
+    game: &'a mut GameState,
+    tt: &'a TranspositionTable,
+    limits: Limits,
+    stop: &'a AtomicBool,
+    start: Instant,
+    nodes: u64,
+    // quiet moves that caused a beta cutoff, by ply, tried early in sibling positions
+    killers: [[Option<Move>; 2]; MAX_PLY],
+    pawns: &'a mut PawnTable,
+    // score of the root move in the pv, kept for when the iteration gets cut short
+    root_score: i32,
+    // set once a limit is hit, after which every score coming back up is meaningless
+    aborted: bool,
+}
+
+/// Search the position until a limit is hit or `stop` is set, calling `info` after every
+/// completed depth. When a limit cuts the first depth short, the best move found so far gets
+/// played, so there's always a move to play.
+pub fn search(
+    game: &mut GameState,
+    limits: Limits,
+    tt: &TranspositionTable,
+    pawns: &mut PawnTable,
+    stop: &AtomicBool,
+    mut info: impl FnMut(&SearchResult),
+) -> SearchResult {
+    tt.new_search();
+    let mut searcher = Searcher {
+        game,
+        tt,
+        limits,
+        stop,
+        start: Instant::now(),
+        nodes: 0,
+        killers: [[None; 2]; MAX_PLY],
+        pawns,
+        root_score: 0,
+        aborted: false,
+    };
+
+    let max_depth = limits.depth.unwrap_or(MAX_PLY as u32).clamp(1, MAX_PLY as u32);
+    let mut result = SearchResult {
+        best_move: None,
+        score: Score::Centipawns(0),
+        pv: Line::new(),
+        depth: 0,
+        nodes: 0,
+        elapsed: Duration::ZERO,
+    };
+
+    for depth in 1..=max_depth {
+        let mut pv = Line::new();
+        let score = searcher.negamax(depth, 0, -INFINITY, INFINITY, &result.pv, &mut pv);
+        if searcher.aborted {
+            // stopped before the first depth finished, which still has to come up with a move
+            if result.best_move.is_none() {
+                if let Some(&mv) = pv.first() {
+                    result.best_move = Some(mv);
+                    result.score = Score::from_internal(searcher.root_score);
+                    result.pv = pv;
+                } else {
+                    result.best_move = searcher.game.legal_moves().first().copied();
+                }
+            }
+            break;
+        }
+
+        result = SearchResult {
+            best_move: pv.first().copied(),
+            score: Score::from_internal(score),
+            pv,
+            depth,
+            nodes: searcher.nodes,
+            elapsed: searcher.start.elapsed(),
+        };
+        info(&result);
+
+        // no point looking deeper once there's nothing to choose between, or once a mate is found
+        // that's no longer than the depth searched. A longer one may have come from the check
+        // evasions in quiescence, and a deeper iteration could still find a quicker mate
+        let proven_mate = matches!(result.score, Score::Mate(_)) && MATE - score.abs() <= depth as i32;
+        if result.best_move.is_none() || proven_mate || searcher.should_stop() {
+            break;
+        }
+    }
+
+    result.nodes = searcher.nodes;
+    result.elapsed = searcher.start.elapsed();
+    result
+}
+
+impl Searcher<'_> {
+    fn should_stop(&self) -> bool {
+        self.stop.load(Ordering::Relaxed)
+            || self.limits.nodes.is_some_and(|nodes| self.nodes >= nodes)
+            || self.limits.time.is_some_and(|time| self.start.elapsed() >= time)
+    }
+
+    /// Score of the position for the side to move, filling `pv` with the line that gets it.
+    /// `prev_pv` is the last iteration's line from here, if we're still on it, and gets tried first.
+    fn negamax(&mut self, depth: u32, ply: usize, mut alpha: i32, beta: i32, prev_pv: &[Move], pv: &mut Line) -> i32 {
+        pv.clear();
+        if ply > 0 && self.is_draw(ply) {
+            return 0;
+        }
+        if depth == 0 || ply >= MAX_PLY {
+            return self.quiesce(ply, alpha, beta);
+        }
+        if !self.visit() {
+            return 0;
+        }
+
+        let key = self.game.key();
+        let entry = self.tt.probe(key, ply);
+        // the root always gets searched, it has to come up with a move
+        if ply > 0
+            && let Some(entry) = entry
+            && entry.depth as u32 >= depth
+        {
+            let usable = match entry.bound {
+                Bound::Exact => true,
+                Bound::Lower => entry.score >= beta,
+                Bound::Upper => entry.score <= alpha,
+            };
+            if usable {
+                return entry.score;
+            }
+        }
+
+        // the last iteration's line is the better guess while we're still on it
+        let pv_move = prev_pv.first().copied();
+        let hash_move = pv_move.or(entry.and_then(|entry| entry.mv));
+        let mut picker = MovePicker::new(self.game, hash_move, self.killers[ply]);
+
+        let original_alpha = alpha;
+        let mut best = -INFINITY;
+        let mut best_move = None;
+        let mut child_pv = Line::new();
+        while let Some(mv) = picker.next(self.game) {
+            let child_prev = if Some(mv) == pv_move { &prev_pv[1..] } else { &[] };
+
+            self.game.make_move(&mv);
+            let score = -self.negamax(depth - 1, ply + 1, -beta, -alpha, child_prev, &mut child_pv);
+            self.game.unmake_move();
+
+            if self.aborted {
+                return 0;
+            }
+
+            if score > best {
+                best = score;
+                best_move = Some(mv);
+                if score > alpha {
+                    alpha = score;
+                    pv.clear();
+                    pv.push(mv);
+                    pv.try_extend_from_slice(&child_pv).ok();
+                    if ply == 0 {
+                        self.root_score = score;
+                    }
+                }
+                if score >= beta {
+                    if !mv.is_capture() && !mv.is_promo() && self.killers[ply][0] != Some(mv) {
+                        self.killers[ply] = [Some(mv), self.killers[ply][0]];
+                    }
+                    break;
+                }
+            }
+        }
+
+        // nothing was played at all
+        if best == -INFINITY {
+            return if self.game.in_check() { -MATE + ply as i32 } else { 0 };
+        }
+
+        let bound = if best >= beta {
+            Bound::Lower
+        } else if best > original_alpha {
+            Bound::Exact
+        } else {
+            Bound::Upper
+        };
+        self.tt.store(key, best_move, best, depth as u8, bound, ply);
+
+        best
+    }
+
+    /// Keep playing captures until the position is quiet, so a leaf isn't scored halfway through
+    /// an exchange. When in check every evasion is searched, so mates at the leaves are still seen.
+    fn quiesce(&mut self, ply: usize, mut alpha: i32, beta: i32) -> i32 {
+        if !self.visit() {
+            return 0;
+        }
+
+        let in_check = self.game.in_check();
+        let stand_pat = evaluate(self.game, self.pawns);
+        if ply >= MAX_PLY {
+            return stand_pat;
+        }
+
+        let mut best = -INFINITY;
+        if !in_check {
+            // the side to move doesn't have to capture, so it's guaranteed at least the static score
+            if stand_pat >= beta {
+                return stand_pat;
+            }
+            alpha = alpha.max(stand_pat);
+            best = stand_pat;
+        }
+
+        let mut picker = MovePicker::captures(self.game);
+        while let Some(mv) = picker.next(self.game) {
+            // skip captures that can't get back to alpha even winning the piece for free
+            if !in_check && stand_pat + gain(self.game, &mv) + DELTA_MARGIN <= alpha {
+                continue;
+            }
+
+            self.game.make_move(&mv);
+            let score = -self.quiesce(ply + 1, -beta, -alpha);
+            self.game.unmake_move();
+
+            if self.aborted {
+                return 0;
+            }
+
+            if score > best {
+                best = score;
+                alpha = alpha.max(score);
+                if score >= beta {
+                    break;
+                }
+            }
+        }
+
+        // in check with no way out
+        if best == -INFINITY {
+            return -MATE + ply as i32;
+        }
+        best
+    }
+
+    /// count a node, returning false once the search has to be abandoned
+    fn visit(&mut self) -> bool {
+        if self.aborted {
+            return false;
+        }
+        // the node limit is a cheap compare, so it's held to exactly
+        if self.limits.nodes.is_some_and(|nodes| self.nodes >= nodes) {
+            self.aborted = true;
+            return false;
+        }
+        self.nodes += 1;
+
+        // only look at the clock and the stop flag every so often
+        if self.nodes.is_multiple_of(1024) && self.should_stop() {
+            self.aborted = true;
+        }
+        !self.aborted
+    }
+
+    /// `ply` plies into the search. A position repeated inside the tree could just be repeated
+    /// again, so once is enough there, but one from before the root needs the full threefold.
+    fn is_draw(&self, ply: usize) -> bool {
+        self.game.repetitions_within(ply) > 0
+            || self.game.is_threefold()
+            || self.game.is_fifty_move_draw()
+            || self.game.is_insufficient_material()
+    }
+}
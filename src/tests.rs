@@ -1,11 +1,20 @@
 #[cfg(test)]
+#[allow(clippy::module_inception)]
 mod tests {
 
+    use crate::bitboard::*;
     use crate::chessboard;
+    use crate::eval::*;
     use crate::game::*;
     use crate::magic::*;
     use crate::movegen::*;
+    use crate::movepick::*;
+    use crate::pawns::*;
+    use crate::search::*;
+    use crate::tt::*;
+    use crate::uci::*;
     use rand::{rngs::StdRng, Rng, SeedableRng};
+    use std::sync::atomic::AtomicBool;
 
     #[test]
     pub fn diagonals_simple() {
@@ -234,6 +243,35 @@ mod tests {
         assert_eq!(span, expected);
     }
 
+    #[test]
+    pub fn straight_blockers_off_ray() {
+        // pieces that aren't on the rook's file or rank must not affect its ray
+        let blockers = chessboard!(
+            0b_00000000
+            0b_01000100
+            0b_00100000
+            0b_00001010
+            0b_10000000
+            0b_00100001
+            0b_00000000
+            0b_00000001
+        );
+        // e5 = (4, 4)
+        let span: u64 = gen_blocked_straight(4, 4, blockers);
+        let expected: u64 = chessboard!(
+            0b_00001000
+            0b_00001000
+            0b_00001000
+            0b_11110110
+            0b_00001000
+            0b_00001000
+            0b_00001000
+            0b_00001000
+        );
+
+        assert_eq!(span, expected);
+    }
+
     #[test]
     pub fn knight() {
         // Old (6, 4) was g4, new coords for g4 = (6, 3)
@@ -306,10 +344,10 @@ mod tests {
             0b_00000000
             0b_00000000
             0b_00000000
-            0b_00000000
             0b_00100000
             0b_00100000
             0b_00000000
+            0b_00000000
         );
 
         let expected_threat_bb: u64 = chessboard!(
@@ -407,6 +445,37 @@ mod tests {
         assert_eq!(move_board, expected_move_bb);
         assert_eq!(threat_board, expected_threat_bb);
 
+        let (move_board, threat_board) = gen_pawn_moves(3, 6, false);
+
+        let expected_move_bb: u64 = chessboard!(
+            0b_00000000
+            0b_00000000
+            0b_00010000
+            0b_00010000
+            0b_00000000
+            0b_00000000
+            0b_00000000
+            0b_00000000
+        );
+
+        let expected_threat_bb: u64 = chessboard!(
+            0b_00000000
+            0b_00000000
+            0b_00101000
+            0b_00000000
+            0b_00000000
+            0b_00000000
+            0b_00000000
+            0b_00000000
+        );
+
+        assert_eq!(move_board, expected_move_bb);
+        assert_eq!(threat_board, expected_threat_bb);
+
+        // captures onto the bottom row next to the h file
+        let (_, threat_board) = gen_pawn_moves(6, 1, false);
+        assert_eq!(threat_board, coords_to_bb(5, 0) | coords_to_bb(7, 0));
+
         let (move_board, threat_board) = gen_pawn_moves(7, 7, true);
 
         print_bitboard(move_board);
@@ -415,6 +484,536 @@ mod tests {
         assert_eq!(move_board, 0);
         assert_eq!(threat_board, 0);
     }
+
+    #[test]
+    pub fn square_names() {
+        let e4: Square = "e4".parse().unwrap();
+        assert_eq!(e4, Square::new(4, 3));
+        assert_eq!(e4.coords(), (4, 3));
+        assert_eq!(e4.bb().0, coords_to_bb(4, 3));
+        assert_eq!(e4.to_string(), "e4");
+        assert_eq!("a1".parse::<Square>().unwrap().bb().0, coords_to_bb(0, 0));
+        assert_eq!("h8".parse::<Square>().unwrap().bb().0, coords_to_bb(7, 7));
+
+        for bad in ["", "e", "e9", "i4", "E4", "e44"] {
+            assert_eq!(bad.parse::<Square>(), Err(ParseSquareError));
+        }
+    }
+
+    #[test]
+    pub fn bitboard_shifts() {
+        let corners = Square::new(0, 0).bb() | Square::new(7, 0) | Square::new(0, 7) | Square::new(7, 7);
+
+        // every direction keeps exactly the corners that have a neighbour that way
+        assert_eq!(corners.shift(Direction::Up), Square::new(0, 1).bb() | Square::new(7, 1));
+        assert_eq!(corners.shift(Direction::Down), Square::new(0, 6).bb() | Square::new(7, 6));
+        assert_eq!(corners.shift(Direction::Left), Square::new(6, 0).bb() | Square::new(6, 7));
+        assert_eq!(corners.shift(Direction::Right), Square::new(1, 0).bb() | Square::new(1, 7));
+        assert_eq!(corners.shift(Direction::UpLeft), Square::new(6, 1).bb());
+        assert_eq!(corners.shift(Direction::UpRight), Square::new(1, 1).bb());
+        assert_eq!(corners.shift(Direction::DownLeft), Square::new(6, 6).bb());
+        assert_eq!(corners.shift(Direction::DownRight), Square::new(1, 6).bb());
+
+        // whole files don't wrap onto the next rank
+        assert_eq!(Bitboard::file(7).shift(Direction::Right), Bitboard::EMPTY);
+        assert_eq!(Bitboard::file(0).shift(Direction::Left), Bitboard::EMPTY);
+        assert_eq!(Bitboard::file(3).shift(Direction::Right), Bitboard::file(4));
+
+        assert_eq!(Square::new(4, 3).shift(Direction::UpLeft), Some(Square::new(3, 4)));
+        assert_eq!(Square::new(0, 3).shift(Direction::Left), None);
+    }
+
+    #[test]
+    pub fn bitboard_squares() {
+        let squares = [Square::new(0, 0), Square::new(4, 3), Square::new(7, 7)];
+        let bb: Bitboard = squares.into_iter().collect();
+
+        assert_eq!(bb.count(), 3);
+        assert_eq!(bb.into_iter().len(), 3);
+        assert!(bb.contains(Square::new(4, 3)) && !bb.contains(Square::new(3, 4)));
+
+        let mut found: Vec<Square> = bb.into_iter().collect();
+        found.sort();
+        let mut expected = squares.to_vec();
+        expected.sort();
+        assert_eq!(found, expected);
+
+        assert_eq!(bb & Square::new(4, 3), Square::new(4, 3).bb());
+        assert_eq!((bb ^ Square::new(4, 3)).count(), 2);
+        assert_eq!((!bb).count(), 61);
+        assert_eq!(Bitboard::rank(0) & Bitboard::file(0), Square::new(0, 0).bb());
+        assert!(Bitboard::EMPTY.first().is_none());
+    }
+
+    // node counts from https://www.chessprogramming.org/Perft_Results
+    const STARTPOS: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+    const KIWIPETE: &str = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+    const POSITION_3: &str = "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1";
+    const POSITION_4: &str = "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1";
+    const POSITION_5: &str = "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8";
+    const POSITION_6: &str = "r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 w - - 0 10";
+
+    fn assert_perft(fen: &str, expected: &[u64]) {
+        let mut game = GameState::try_from_fen(fen).unwrap();
+        game.init_magics();
+
+        for (depth, &nodes) in expected.iter().enumerate() {
+            assert_eq!(game.perft(depth as u32 + 1), nodes, "perft({}) of {fen}", depth + 1);
+        }
+    }
+
+    #[test]
+    pub fn perft() {
+        assert_perft(STARTPOS, &[20, 400, 8902, 197281]);
+        assert_perft(KIWIPETE, &[48, 2039, 97862, 4085603]);
+        assert_perft(POSITION_3, &[14, 191, 2812, 43238]);
+        assert_perft(POSITION_4, &[6, 264, 9467, 422333]);
+        assert_perft(POSITION_5, &[44, 1486, 62379, 2103487]);
+        assert_perft(POSITION_6, &[46, 2079, 89890, 3894594]);
+    }
+
+    #[test]
+    #[ignore = "takes a while, run with --ignored"]
+    pub fn perft_deep() {
+        assert_perft(STARTPOS, &[20, 400, 8902, 197281, 4865609, 119060324]);
+        assert_perft(KIWIPETE, &[48, 2039, 97862, 4085603, 193690690]);
+        assert_perft(POSITION_3, &[14, 191, 2812, 43238, 674624, 11030083]);
+        assert_perft(POSITION_4, &[6, 264, 9467, 422333, 15833292]);
+        assert_perft(POSITION_5, &[44, 1486, 62379, 2103487, 89941194]);
+        assert_perft(POSITION_6, &[46, 2079, 89890, 3894594, 164075551]);
+    }
+
+    #[test]
+    pub fn perft_divide() {
+        let mut game = GameState::try_from_fen(KIWIPETE).unwrap();
+        game.init_magics();
+
+        let divided = game.perft_divide(3);
+        assert_eq!(divided.len(), 48);
+        assert_eq!(divided.iter().map(|(_, nodes)| nodes).sum::<u64>(), 97862);
+
+        let castle = divided.iter().find(|(mv, _)| mv.to_string() == "e1g1").unwrap();
+        assert_eq!(castle.1, 2059);
+    }
+
+    /// the same position with the colors swapped and the board flipped
+    fn mirror_fen(fen: &str) -> String {
+        let fields: Vec<&str> = fen.split(' ').collect();
+        let swap_case = |field: &str| field.chars().map(|ch| if ch.is_ascii_uppercase() { ch.to_ascii_lowercase() } else { ch.to_ascii_uppercase() }).collect::<String>();
+
+        let placement = fields[0].split('/').rev().map(swap_case).collect::<Vec<_>>().join("/");
+        let turn = if fields[1] == "w" { "b" } else { "w" };
+        let castling = swap_case(fields[2]);
+        let en_passe = fields[3].replace('3', "x").replace('6', "3").replace('x', "6");
+
+        format!("{placement} {turn} {castling} {en_passe} {} {}", fields[4], fields[5])
+    }
+
+    #[test]
+    pub fn eval_symmetry() {
+        let mut pawns = PawnTable::default();
+        let mut eval = |fen: &str| evaluate(&GameState::try_from_fen(fen).unwrap(), &mut pawns);
+
+        assert_eq!(eval(STARTPOS), 0);
+        for fen in [KIWIPETE, POSITION_3, POSITION_4, POSITION_5, POSITION_6] {
+            assert_eq!(eval(fen), eval(&mirror_fen(fen)), "{fen}");
+        }
+
+        // the same board is as good for one side as it's bad for the other
+        let fen = "r1bqkb1r/pppp1ppp/2n2n2/4p3/2B1P3/5N2/PPPP1PPP/RNBQK2R w KQkq - 4 4";
+        assert_eq!(eval(fen), -eval(&fen.replace(" w ", " b ")));
+    }
+
+    #[test]
+    pub fn eval_terms() {
+        let mut pawns = PawnTable::default();
+        let mut eval = |fen: &str| evaluate(&GameState::try_from_fen(fen).unwrap(), &mut pawns);
+
+        // an extra queen is worth about a queen
+        let queen_up = eval("rnb1kbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1");
+        assert!((850..1100).contains(&queen_up), "{queen_up}");
+        assert_eq!(piece_value(PieceType::Queen), 1025);
+
+        // developed knights beat ones sitting at home
+        assert!(eval("r1bqkbnr/pppppppp/2n5/8/8/2N2N2/PPPPPPPP/R1BQKB1R b KQkq - 0 1") < 0);
+
+        // with the board empty the king belongs in the middle, not tucked in a corner
+        let central = eval("8/8/8/4K3/8/8/4P3/7k w - - 0 1");
+        let cornered = eval("K7/8/8/8/8/8/4P3/7k w - - 0 1");
+        assert!(central > cornered + 50, "{central} {cornered}");
+
+        // while there's still material around it should stay home
+        let castled = eval("r1bq1rk1/pppp1ppp/2n2n2/2b1p3/2B1P3/2N2N2/PPPP1PPP/R1BQ1RK1 w - - 0 1");
+        let wandering = eval("r1bq1rk1/pppp1ppp/2n2n2/2b1p3/2B1P3/2NK1N2/PPPP1PPP/R1BQ1R2 w - - 0 1");
+        assert!(castled > wandering, "{castled} {wandering}");
+    }
+
+    #[test]
+    pub fn pawn_structure_terms() {
+        let structure = |fen: &str| pawn_structure(&GameState::try_from_fen(fen).unwrap());
+        let mg = |fen: &str| structure(fen).0;
+        let eg = |fen: &str| structure(fen).1;
+
+        // the starting pawns and any mirrored structure are even
+        assert_eq!(structure("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1"), (0, 0));
+        assert_eq!(structure("4k3/pp3ppp/2p5/3p4/3P4/2P5/PP3PPP/4K3 w - - 0 1"), (0, 0));
+
+        // doubled pawns are worse than the same pawns on neighbouring files
+        assert!(eg("4k3/8/8/8/8/4P3/4P3/4K3 w - - 0 1") < eg("4k3/8/8/8/8/8/3PP3/4K3 w - - 0 1"));
+
+        // an isolated pawn is worse than one with a neighbour further up, both passed either way
+        assert!(eg("4k3/8/8/8/6P1/8/3P4/4K3 w - - 0 1") < eg("4k3/8/8/8/4P3/8/3P4/4K3 w - - 0 1"));
+
+        // d3 can't be defended by the e4 pawn and c5 watches d4, so it's backward. From c6 it doesn't
+        assert!(mg("4k3/8/8/2p5/4P3/3P4/8/4K3 w - - 0 1") < mg("4k3/8/2p5/8/4P3/3P4/8/4K3 w - - 0 1"));
+
+        // passed pawns are worth more the further they've got
+        let passed = |y: usize| {
+            let mut ranks = ["4k3", "8", "8", "8", "8", "8", "8", "4K3"];
+            ranks[7 - y] = "P7";
+            eg(&format!("{} w - - 0 1", ranks.join("/")))
+        };
+        assert!((2..=6).all(|y| passed(y) > passed(y - 1)));
+        // but not while an enemy pawn can still stop them
+        assert!(eg("4k3/1p6/P7/8/8/8/8/4K3 w - - 0 1") < passed(5));
+
+        // pawns side by side or defending each other beat ones standing apart
+        let apart = mg("4k3/8/8/8/8/8/P1P5/4K3 w - - 0 1");
+        assert!(mg("4k3/8/8/8/8/8/PP6/4K3 w - - 0 1") > apart);
+        assert!(mg("4k3/8/8/8/8/1P6/P7/4K3 w - - 0 1") > apart);
+
+        // black's structure counts against white
+        assert!(eg("4k3/4p3/4p3/8/8/8/8/4K3 w - - 0 1") > 0);
+    }
+
+    #[test]
+    pub fn pawn_table() {
+        let mut table = PawnTable::new(4);
+        for fen in [STARTPOS, KIWIPETE, POSITION_3, POSITION_4, POSITION_5, POSITION_6] {
+            let game = GameState::try_from_fen(fen).unwrap();
+            // once to fill the slot and once to hit it
+            assert_eq!(table.probe(&game), pawn_structure(&game), "{fen}");
+            assert_eq!(table.probe(&game), pawn_structure(&game), "{fen}");
+        }
+
+        // positions with the same pawns share an entry, whatever else is on the board
+        let a = GameState::try_from_fen("r3k3/pp6/8/8/8/8/PP6/4K2R w - - 0 1").unwrap();
+        let b = GameState::try_from_fen("4k3/pp6/8/8/8/8/PP6/R3K3 b - - 0 1").unwrap();
+        assert_eq!(a.pawn_key(), b.pawn_key());
+        assert_ne!(a.key(), b.key());
+        assert_eq!(table.probe(&a), table.probe(&b));
+
+        // a cleared table works things out again
+        table.clear();
+        assert_eq!(table.probe(&a), pawn_structure(&a));
+    }
+
+    fn picked(game: &GameState, mut picker: MovePicker) -> Vec<String> {
+        let mut moves = Vec::new();
+        while let Some(mv) = picker.next(game) {
+            moves.push(mv.to_string());
+        }
+        moves
+    }
+
+    #[test]
+    pub fn move_picker() {
+        // Nxh3 wins a knight, Rxd5 gives up the rook for a pawn
+        let mut game = GameState::try_from_fen("4k3/8/4p3/3p4/8/7n/8/3RK1N1 w - - 0 1").unwrap();
+        game.init_magics();
+
+        let hash = game.parse_uci_move("e1e2").ok();
+        let killers = [game.parse_uci_move("g1f3").ok(), hash];
+        let moves = picked(&game, MovePicker::new(&game, hash, killers));
+
+        assert_eq!(moves[..3], ["e1e2", "g1h3", "g1f3"]);
+        assert_eq!(moves.last().unwrap(), "d1d5");
+
+        // every legal move exactly once
+        let mut sorted = moves.clone();
+        sorted.sort();
+        let mut legal: Vec<String> = game.legal_moves().iter().map(|mv| mv.to_string()).collect();
+        legal.sort();
+        assert_eq!(sorted, legal);
+
+        // a hash move or killer that isn't legal here is passed over
+        let bogus = Some(Move::new((0, 6), (0, 5)));
+        let moves = picked(&game, MovePicker::new(&game, bogus, [bogus, None]));
+        assert_eq!(moves.len(), legal.len());
+        assert_eq!(moves[0], "g1h3");
+
+        assert_eq!(picked(&game, MovePicker::captures(&game)), ["g1h3", "d1d5"]);
+
+        // in check, quiescence gets the evasions too
+        let mut game = GameState::try_from_fen("4k3/8/8/8/8/p4n2/8/1N2KR2 w - - 0 1").unwrap();
+        game.init_magics();
+        let mut moves = picked(&game, MovePicker::captures(&game));
+        assert_eq!(moves[0], "f1f3");
+        moves.sort();
+        let mut legal: Vec<String> = game.legal_moves().iter().map(|mv| mv.to_string()).collect();
+        legal.sort();
+        assert_eq!(moves, legal);
+    }
+
+    fn search_fen(fen: &str, limits: Limits) -> SearchResult {
+        let mut game = GameState::try_from_fen(fen).unwrap();
+        game.init_magics();
+
+        let result = search(&mut game, limits, &TranspositionTable::new(1), &mut PawnTable::default(), &AtomicBool::new(false), |_| {});
+        // the search leaves the position as it found it
+        assert_eq!(game.to_fen(), fen);
+        result
+    }
+
+    #[test]
+    pub fn search_mates() {
+        let depth = |depth| Limits { depth: Some(depth), ..Default::default() };
+
+        // rook roller, there's more than one way to do it
+        let fen = "7k/8/8/8/8/8/R7/1R5K w - - 0 1";
+        let result = search_fen(fen, depth(5));
+        assert_eq!(result.score, Score::Mate(2));
+        // stops once the search is as deep as the mate is long, three plies here
+        assert_eq!(result.depth, 3);
+
+        let mut game = GameState::try_from_fen(fen).unwrap();
+        game.init_magics();
+        for mv in &result.pv {
+            game.make_move(mv);
+        }
+        assert!(game.is_checkmate());
+
+        let result = search_fen("7k/R7/8/8/8/8/8/1R5K b - - 0 1", depth(5));
+        assert_eq!(result.score, Score::Mate(-1));
+        assert!(result.depth >= 2);
+
+        // the check evasions in quiescence already see this mate in 3 at depth 3, but it takes
+        // depth 5 to be sure there's nothing quicker
+        let result = search_fen("r5rk/5p1p/5R2/4B3/8/8/7P/7K w - - 0 1", depth(7));
+        assert_eq!(result.score, Score::Mate(3));
+        assert_eq!(result.depth, 5);
+
+        // nothing to play when stalemated
+        let result = search_fen("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1", depth(5));
+        assert_eq!(result.best_move, None);
+        assert_eq!(result.score, Score::Centipawns(0));
+    }
+
+    #[test]
+    pub fn search_material() {
+        let limits = Limits { depth: Some(3), ..Default::default() };
+        let result = search_fen("4k3/8/8/3q4/8/8/3R4/4K3 w - - 0 1", limits);
+        assert_eq!(result.best_move.unwrap().to_string(), "d2d5");
+        // up a rook, give or take where the pieces stand
+        assert!(matches!(result.score, Score::Centipawns(400..=600)), "{:?}", result.score);
+
+        // a defended rook isn't worth a queen
+        let result = search_fen("4k3/8/4p3/3r4/8/8/3Q4/4K3 w - - 0 1", limits);
+        assert_ne!(result.best_move.unwrap().to_string(), "d2d5");
+    }
+
+    #[test]
+    pub fn search_quiescence() {
+        let limits = Limits { depth: Some(1), ..Default::default() };
+
+        // the pawn on d5 is defended, which one ply alone can't see
+        let result = search_fen("4k3/8/2p5/3p4/8/8/8/3QK3 w - - 0 1", limits);
+        assert_ne!(result.best_move.unwrap().to_string(), "d1d5");
+
+        // but a defended knight is still worth a pawn
+        let result = search_fen("4k3/8/2p5/3n4/4P3/8/8/4K3 w - - 0 1", limits);
+        assert_eq!(result.best_move.unwrap().to_string(), "e4d5");
+
+        // the leaves still notice when they're mated
+        let result = search_fen("6k1/5ppp/8/8/8/8/5PPP/4R1K1 w - - 0 1", limits);
+        assert_eq!(result.best_move.unwrap().to_string(), "e1e8");
+        assert_eq!(result.score, Score::Mate(1));
+    }
+
+    #[test]
+    pub fn transposition_table() {
+        // rounds up to a single bucket of four slots, so every key competes for them
+        let tt = TranspositionTable::new(0);
+        let mv = Move::new((4, 1), (4, 3));
+
+        for key in 1..=4 {
+            tt.store(key, None, key as i32 * 10, key as u8, Bound::Exact, 0);
+        }
+        assert_eq!(tt.probe(3, 0), Some(TtEntry { mv: None, score: 30, depth: 3, bound: Bound::Exact }));
+        assert_eq!(tt.probe(9, 0), None);
+
+        // the same position keeps its move when a later search doesn't come up with one
+        tt.store(3, Some(mv), -15, 4, Bound::Upper, 0);
+        tt.store(3, None, 25, 5, Bound::Lower, 0);
+        assert_eq!(tt.probe(3, 0), Some(TtEntry { mv: Some(mv), score: 25, depth: 5, bound: Bound::Lower }));
+
+        // a full bucket gives up its shallowest entry
+        tt.store(5, None, 0, 6, Bound::Exact, 0);
+        assert_eq!(tt.probe(1, 0), None);
+        assert!([2, 3, 4, 5].iter().all(|&key| tt.probe(key, 0).is_some()));
+        assert_eq!(tt.hashfull(), 1000);
+
+        // unless it's from an earlier search, which counts for less than a few plies of depth
+        tt.new_search();
+        tt.store(6, None, 0, 1, Bound::Exact, 0);
+        assert_eq!(tt.probe(2, 0), None);
+        assert!([3, 4, 5, 6].iter().all(|&key| tt.probe(key, 0).is_some()));
+        assert_eq!(tt.hashfull(), 250);
+
+        tt.clear();
+        assert!((1..=6).all(|key| tt.probe(key, 0).is_none()));
+        assert_eq!(tt.hashfull(), 0);
+    }
+
+    #[test]
+    pub fn transposition_table_mates() {
+        let tt = TranspositionTable::new(1);
+
+        // mated in 6 plies from a position 4 plies into the search is mate in 4 plies from there,
+        // which is mate in 6 plies from the root when it turns up again 2 plies in
+        tt.store(7, None, -crate::search::MATE + 10, 8, Bound::Exact, 4);
+        assert_eq!(tt.probe(7, 2).unwrap().score, -crate::search::MATE + 8);
+        tt.store(8, None, crate::search::MATE - 10, 8, Bound::Exact, 4);
+        assert_eq!(tt.probe(8, 2).unwrap().score, crate::search::MATE - 8);
+
+        // the table carries over between searches without changing the answer
+        let fen = "7k/8/8/8/8/8/R7/1R5K w - - 0 1";
+        let mut game = GameState::try_from_fen(fen).unwrap();
+        game.init_magics();
+        let limits = Limits { depth: Some(5), ..Default::default() };
+        let mut pawns = PawnTable::default();
+        for _ in 0..2 {
+            let result = search(&mut game, limits, &tt, &mut pawns, &AtomicBool::new(false), |_| {});
+            assert_eq!(result.score, Score::Mate(2));
+            assert!(result.best_move.is_some());
+        }
+        assert_eq!(tt.probe(game.key(), 0).unwrap().bound, Bound::Exact);
+    }
+
+    #[test]
+    pub fn search_limits() {
+        let fen = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+
+        let mut depths = Vec::new();
+        let mut game = GameState::try_from_fen(fen).unwrap();
+        game.init_magics();
+        let limits = Limits { depth: Some(3), ..Default::default() };
+        let result = search(&mut game, limits, &TranspositionTable::new(1), &mut PawnTable::default(), &AtomicBool::new(false), |info| depths.push(info.depth));
+        assert_eq!(depths, [1, 2, 3]);
+        assert_eq!(result.depth, 3);
+
+        // the stop flag is seen within the first depth too, and there's still a move to play
+        let mut game = GameState::try_from_fen(fen).unwrap();
+        game.init_magics();
+        let result = search(&mut game, Limits::default(), &TranspositionTable::new(1), &mut PawnTable::default(), &AtomicBool::new(true), |_| {});
+        assert_eq!(result.depth, 0);
+        assert_eq!(result.nodes, 1024);
+        assert!(result.best_move.is_some());
+        // along with the score and line of the best root move searched by then
+        assert_eq!(result.pv.first().copied(), result.best_move);
+        assert_ne!(result.score, Score::Centipawns(0));
+
+        let result = search_fen(fen, Limits { nodes: Some(5000), ..Default::default() });
+        assert_eq!(result.nodes, 5000);
+        assert!(result.best_move.is_some());
+
+        // the node limit can cut the first depth short, there's still a move to play
+        for nodes in [0, 1, 10] {
+            let result = search_fen(fen, Limits { nodes: Some(nodes), ..Default::default() });
+            assert_eq!(result.nodes, nodes);
+            assert!(result.best_move.is_some());
+            // too few to finish a single root move, so there's no line to go with it
+            assert_eq!(result.depth, 0);
+            assert!(result.pv.is_empty());
+        }
+    }
+
+    fn go(command: &str) -> GoParams {
+        GoParams::parse(command.split_whitespace().skip(1))
+    }
+
+    #[test]
+    pub fn go_params() {
+        let params = go("go wtime 60000 btime 55000 winc 1000 binc 500 movestogo 20");
+        assert_eq!(
+            params,
+            GoParams {
+                wtime: Some(60000),
+                btime: Some(55000),
+                winc: Some(1000),
+                binc: Some(500),
+                movestogo: Some(20),
+                ..GoParams::default()
+            }
+        );
+
+        assert_eq!(go("go depth 6"), GoParams { depth: Some(6), ..GoParams::default() });
+        assert_eq!(go("go nodes 5000 movetime 100"), GoParams { nodes: Some(5000), movetime: Some(100), ..GoParams::default() });
+        assert_eq!(go("go infinite"), GoParams { infinite: true, ..GoParams::default() });
+        assert_eq!(go("go"), GoParams::default());
+
+        // unknown tokens are skipped, values that don't parse or are missing are left out
+        assert_eq!(go("go ponder searchmoves e2e4 depth 3"), GoParams { depth: Some(3), ..GoParams::default() });
+        assert_eq!(go("go depth -1 nodes lots"), GoParams::default());
+        assert_eq!(go("go movetime"), GoParams::default());
+    }
+
+    #[test]
+    pub fn time_budget() {
+        let budget = |command: &str, side| go(command).time_budget(side).map(|time| time.as_millis());
+
+        // a depth or node limit and no clock, or told to go on forever
+        assert_eq!(budget("go depth 5", Color::White), None);
+        assert_eq!(budget("go nodes 5000", Color::White), None);
+        assert_eq!(budget("go infinite wtime 1000", Color::White), None);
+
+        // a bare go gets a fixed time rather than searching as deep as it can
+        assert_eq!(budget("go", Color::White), Some(5000));
+        assert_eq!(budget("go", Color::Black), Some(5000));
+        // only the side to move's clock counts, so that's a bare go for white too
+        assert_eq!(budget("go btime 1000", Color::White), Some(5000));
+
+        // a fixed move time overrides the clock
+        assert_eq!(budget("go wtime 60000 movetime 250", Color::White), Some(250));
+
+        // the clock is spread over the moves left, 30 when the GUI doesn't say, and at least 1
+        assert_eq!(budget("go wtime 60000", Color::White), Some(2000));
+        assert_eq!(budget("go btime 60000 movestogo 10", Color::Black), Some(6000));
+        assert_eq!(budget("go wtime 1000 movestogo 0", Color::White), Some(950));
+
+        // three quarters of the increment is added
+        assert_eq!(budget("go wtime 60000 winc 1000 binc 4000", Color::White), Some(2750));
+        assert_eq!(budget("go btime 60000 winc 1000 binc 4000", Color::Black), Some(5000));
+
+        // 50ms is always left on the clock, so under that there's no time at all
+        assert_eq!(budget("go wtime 300 winc 1000", Color::White), Some(250));
+        assert_eq!(budget("go wtime 30", Color::White), Some(0));
+
+        let limits = go("go wtime 60000 depth 4 nodes 100").limits(Color::White);
+        assert_eq!(limits, Limits { depth: Some(4), nodes: Some(100), time: Some(std::time::Duration::from_millis(2000)) });
+    }
+
+    #[test]
+    pub fn uci_position() {
+        let mut uci = Uci::new();
+        let fen = |uci: &Uci| uci.game.as_ref().unwrap().to_fen();
+
+        uci.handle("position startpos moves e2e4 e7e5 g1f3");
+        assert_eq!(fen(&uci), "rnbqkbnr/pppp1ppp/8/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R b KQkq - 1 2");
+
+        uci.handle("position fen 4k3/8/8/8/8/8/8/R3K3 w Q - 0 1 moves e1c1");
+        assert_eq!(fen(&uci), "4k3/8/8/8/8/8/8/2KR4 b - - 1 1");
+
+        // an illegal move partway through plays none of them
+        uci.handle("position startpos moves e2e4 e7e5 e1e3 g1f3");
+        assert_eq!(fen(&uci), STARTPOS);
+        uci.handle("position fen 4k3/8/8/8/8/8/8/R3K3 w Q - 0 1 moves a1a8 e8d8");
+        assert_eq!(fen(&uci), "4k3/8/8/8/8/8/8/R3K3 w Q - 0 1");
+
+        // and a bad fen leaves the last position alone
+        uci.handle("position fen 4k3/8/8 w - - 0 1 moves a1a8");
+        assert_eq!(fen(&uci), "4k3/8/8/8/8/8/8/R3K3 w Q - 0 1");
+    }
 }
 
 // 0 0 0 0 0 0 0 0
@@ -0,0 +1,200 @@
+// transposition table: remembers what earlier searches found out about positions, keyed by
+// zobrist key. Slots are plain atomics so searches on other threads can share one table; a slot
+// torn by two threads writing at once just fails the key check, since the key is stored xored
+// with the data.
+
+use std::sync::atomic::{AtomicU8, AtomicU64, Ordering};
+
+use crate::game::Move;
+use crate::search::{MATE, MAX_PLY};
+
+/// How a stored score relates to the real one
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Bound {
+    Exact,
+    /// the real score is at least this, the search failed high
+    Lower,
+    /// the real score is at most this, the search failed low
+    Upper,
+}
+
+/// What the table knows about a position
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TtEntry {
+    pub mv: Option<Move>,
+    /// already adjusted so mate scores count from the probing position's root
+    pub score: i32,
+    pub depth: u8,
+    pub bound: Bound,
+}
+
+const BUCKET_SIZE: usize = 4;
+// ages wrap around within 6 bits
+const AGE_MASK: u8 = 0x3F;
+
+#[derive(Default)]
+struct Slot {
+    key: AtomicU64,
+    data: AtomicU64,
+}
+
+// one cache line's worth of slots
+#[derive(Default)]
+#[repr(align(64))]
+struct Bucket([Slot; BUCKET_SIZE]);
+
+/// A fixed size table of buckets, a position's key picking the bucket
+pub struct TranspositionTable {
+    buckets: Vec<Bucket>,
+    age: AtomicU8,
+}
+
+// data is laid out as move (16 bits), score (16), depth (8), bound (2), age (6)
+fn pack(mv: Option<Move>, score: i32, depth: u8, bound: Bound, age: u8) -> u64 {
+    let mv = mv.map_or(0, |mv| mv.to_bits()) as u64;
+    let score = score as i16 as u16 as u64;
+    // zero is left for empty slots
+    let bound = match bound {
+        Bound::Exact => 1,
+        Bound::Lower => 2,
+        Bound::Upper => 3,
+    };
+
+    mv | (score << 16) | ((depth as u64) << 32) | (bound << 40) | (((age & AGE_MASK) as u64) << 42)
+}
+
+fn unpack(data: u64) -> Option<(TtEntry, u8)> {
+    let bound = match (data >> 40) & 0b11 {
+        1 => Bound::Exact,
+        2 => Bound::Lower,
+        3 => Bound::Upper,
+        _ => return None,
+    };
+    let mv = data as u16;
+    let entry = TtEntry {
+        mv: if mv == 0 { None } else { Some(Move::from_bits(mv)) },
+        score: (data >> 16) as u16 as i16 as i32,
+        depth: (data >> 32) as u8,
+        bound,
+    };
+
+    Some((entry, (data >> 42) as u8 & AGE_MASK))
+}
+
+// mates are stored as plies from the position itself rather than from the root,
+// so they stay right when the position turns up at another ply
+fn score_to_tt(score: i32, ply: usize) -> i32 {
+    if score >= MATE - MAX_PLY as i32 {
+        score + ply as i32
+    } else if score <= -MATE + MAX_PLY as i32 {
+        score - ply as i32
+    } else {
+        score
+    }
+}
+
+fn score_from_tt(score: i32, ply: usize) -> i32 {
+    if score >= MATE - MAX_PLY as i32 {
+        score - ply as i32
+    } else if score <= -MATE + MAX_PLY as i32 {
+        score + ply as i32
+    } else {
+        score
+    }
+}
+
+impl TranspositionTable {
+    /// A table taking up about `mb` megabytes, and at least one bucket
+    pub fn new(mb: usize) -> Self {
+        let count = (mb * 1024 * 1024 / size_of::<Bucket>()).max(1);
+        TranspositionTable {
+            buckets: (0..count).map(|_| Bucket::default()).collect(),
+            age: AtomicU8::new(0),
+        }
+    }
+
+    pub fn clear(&self) {
+        for slot in self.buckets.iter().flat_map(|bucket| &bucket.0) {
+            slot.key.store(0, Ordering::Relaxed);
+            slot.data.store(0, Ordering::Relaxed);
+        }
+        self.age.store(0, Ordering::Relaxed);
+    }
+
+    /// Called once per search, so entries from earlier ones get replaced first
+    pub fn new_search(&self) {
+        let age = self.age.load(Ordering::Relaxed);
+        self.age.store(age.wrapping_add(1) & AGE_MASK, Ordering::Relaxed);
+    }
+
+    fn bucket(&self, key: u64) -> &Bucket {
+        // the high bits of key * len spread keys evenly over any number of buckets
+        let index = ((key as u128 * self.buckets.len() as u128) >> 64) as usize;
+        &self.buckets[index]
+    }
+
+    /// Look up a position, `ply` being how far it is from the root of the search
+    pub fn probe(&self, key: u64, ply: usize) -> Option<TtEntry> {
+        self.bucket(key).0.iter().find_map(|slot| {
+            let data = slot.data.load(Ordering::Relaxed);
+            if slot.key.load(Ordering::Relaxed) ^ data != key {
+                return None;
+            }
+
+            let (mut entry, _) = unpack(data)?;
+            entry.score = score_from_tt(entry.score, ply);
+            Some(entry)
+        })
+    }
+
+    /// Remember a search result. The position's own slot is overwritten, otherwise the slot
+    /// holding the shallowest and oldest result in the bucket
+    pub fn store(&self, key: u64, mv: Option<Move>, score: i32, depth: u8, bound: Bound, ply: usize) {
+        let bucket = self.bucket(key);
+        let age = self.age.load(Ordering::Relaxed);
+
+        let mut victim = &bucket.0[0];
+        let mut victim_worth = i32::MAX;
+        let mut mv = mv;
+        for slot in &bucket.0 {
+            let data = slot.data.load(Ordering::Relaxed);
+            let stored = unpack(data);
+
+            if let Some((entry, _)) = stored
+                && slot.key.load(Ordering::Relaxed) ^ data == key
+            {
+                // keep the old best move rather than forget it
+                mv = mv.or(entry.mv);
+                victim = slot;
+                break;
+            }
+
+            // every search an entry sits through costs it as much as a few plies of depth,
+            // and empty slots are worth nothing at all
+            let worth = stored.map_or(i32::MIN, |(entry, entry_age)| {
+                let staleness = (age.wrapping_sub(entry_age) & AGE_MASK) as i32;
+                entry.depth as i32 - 8 * staleness
+            });
+            if worth < victim_worth {
+                victim = slot;
+                victim_worth = worth;
+            }
+        }
+
+        let data = pack(mv, score_to_tt(score, ply), depth, bound, age);
+        victim.key.store(key ^ data, Ordering::Relaxed);
+        victim.data.store(data, Ordering::Relaxed);
+    }
+
+    /// How full the table is in permille, counting entries written during the current search
+    pub fn hashfull(&self) -> u32 {
+        let age = self.age.load(Ordering::Relaxed);
+        let sample = self.buckets.iter().take(1000 / BUCKET_SIZE).flat_map(|bucket| &bucket.0);
+
+        let (used, total) = sample.fold((0, 0), |(used, total), slot| {
+            let current = unpack(slot.data.load(Ordering::Relaxed)).is_some_and(|(_, entry_age)| entry_age == age);
+            (used + current as u32, total + 1)
+        });
+        used * 1000 / total.max(1)
+    }
+}
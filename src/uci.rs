@@ -0,0 +1,287 @@
+// UCI front-end: commands come in on stdin, answers go out on stdout
+// protocol reference: https://www.wbec-ridderkerk.nl/html/UCIProtocol.html
+
+use std::io::{self, BufRead};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use crate::game::{Color, GameState};
+use crate::pawns::PawnTable;
+use crate::search::{self, Limits, Score, SearchResult};
+use crate::tt::TranspositionTable;
+
+const STARTPOS: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+// transposition table size in MB
+const DEFAULT_HASH: usize = 16;
+const MAX_HASH: usize = 65536;
+
+// thinking time in milliseconds for a `go` that gives neither a clock nor a depth or node limit
+const DEFAULT_MOVETIME: u64 = 5000;
+
+/// Parameters of a `go` command, times in milliseconds
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct GoParams {
+    pub(crate) wtime: Option<u64>,
+    pub(crate) btime: Option<u64>,
+    pub(crate) winc: Option<u64>,
+    pub(crate) binc: Option<u64>,
+    pub(crate) movestogo: Option<u64>,
+    pub(crate) movetime: Option<u64>,
+    pub(crate) depth: Option<u32>,
+    pub(crate) nodes: Option<u64>,
+    pub(crate) infinite: bool,
+}
+
+impl GoParams {
+    /// unknown tokens and values that don't parse are skipped
+    pub(crate) fn parse<'a>(mut tokens: impl Iterator<Item = &'a str>) -> Self {
+        let mut params = GoParams::default();
+
+        while let Some(token) = tokens.next() {
+            if token == "infinite" {
+                params.infinite = true;
+                continue;
+            }
+
+            let field = match token {
+                "wtime" => &mut params.wtime,
+                "btime" => &mut params.btime,
+                "winc" => &mut params.winc,
+                "binc" => &mut params.binc,
+                "movestogo" => &mut params.movestogo,
+                "movetime" => &mut params.movetime,
+                "nodes" => &mut params.nodes,
+                "depth" => {
+                    params.depth = tokens.next().and_then(|depth| depth.parse().ok());
+                    continue;
+                }
+                _ => continue,
+            };
+            *field = tokens.next().and_then(|value| value.parse().ok());
+        }
+
+        params
+    }
+
+    pub(crate) fn limits(&self, side: Color) -> Limits {
+        Limits { depth: self.depth, nodes: self.nodes, time: self.time_budget(side) }
+    }
+
+    /// How long to think for, or None when the depth or node limit ends the search or it's
+    /// kept going until told to stop
+    pub(crate) fn time_budget(&self, side: Color) -> Option<Duration> {
+        if self.infinite {
+            return None;
+        }
+        if let Some(movetime) = self.movetime {
+            return Some(Duration::from_millis(movetime));
+        }
+
+        let (time, inc) = if side == Color::White { (self.wtime, self.winc) } else { (self.btime, self.binc) };
+        let Some(time) = time else {
+            // with nothing else to stop it, a search would run on to the deepest ply it can
+            let unlimited = self.depth.is_none() && self.nodes.is_none();
+            return unlimited.then(|| Duration::from_millis(DEFAULT_MOVETIME));
+        };
+        // spread the clock over the moves left, guessing when the GUI doesn't say,
+        // and never plan on using all of it so there's some slack for lag
+        let moves_left = self.movestogo.unwrap_or(30).max(1);
+        let budget = time / moves_left + inc.unwrap_or(0) * 3 / 4;
+        Some(Duration::from_millis(budget.min(time.saturating_sub(50))))
+    }
+}
+
+/// A search running on its own thread, which owns the position and pawn table until it's joined
+struct Search {
+    stop: Arc<AtomicBool>,
+    handle: JoinHandle<(GameState, PawnTable)>,
+}
+
+pub struct Uci {
+    // None while a search has them
+    pub(crate) game: Option<GameState>,
+    pawns: Option<PawnTable>,
+    search: Option<Search>,
+    // shared with the search thread while one runs
+    tt: Arc<TranspositionTable>,
+}
+
+impl Uci {
+    pub fn new() -> Self {
+        let mut game = GameState::new();
+        game.init_magics();
+
+        Uci {
+            game: Some(game),
+            pawns: Some(PawnTable::default()),
+            search: None,
+            tt: Arc::new(TranspositionTable::new(DEFAULT_HASH)),
+        }
+    }
+
+    /// Answer commands until "quit" or the end of input
+    pub fn run(&mut self) {
+        for line in io::stdin().lock().lines() {
+            let Ok(line) = line else { break };
+            if !self.handle(&line) {
+                break;
+            }
+        }
+
+        self.stop();
+    }
+
+    /// returns false once it's time to quit
+    pub(crate) fn handle(&mut self, line: &str) -> bool {
+        let mut tokens = line.split_whitespace();
+
+        match tokens.next() {
+            Some("uci") => {
+                println!("id name Knightmare {}", env!("CARGO_PKG_VERSION"));
+                println!("id author Rift");
+                println!("option name Hash type spin default {DEFAULT_HASH} min 1 max {MAX_HASH}");
+                println!("uciok");
+            }
+            Some("isready") => println!("readyok"),
+            Some("setoption") => {
+                self.stop();
+                self.set_option(tokens);
+            }
+            Some("ucinewgame") => {
+                self.stop();
+                self.game_mut().set_fen(STARTPOS).unwrap();
+                self.tt.clear();
+                self.pawns.as_mut().expect("pawn table is owned by a running search").clear();
+            }
+            Some("position") => {
+                self.stop();
+                self.position(tokens);
+            }
+            Some("go") => {
+                self.stop();
+                self.go(GoParams::parse(tokens));
+            }
+            Some("stop") => self.stop(),
+            Some("quit") => return false,
+            Some(command) => println!("info string unknown command {command}"),
+            None => {}
+        }
+
+        true
+    }
+
+    fn game_mut(&mut self) -> &mut GameState {
+        self.game.as_mut().expect("position is owned by a running search")
+    }
+
+    /// `setoption name <name> [value <value>]`, option names being case insensitive
+    fn set_option<'a>(&mut self, mut tokens: impl Iterator<Item = &'a str>) {
+        if tokens.next() != Some("name") {
+            println!("info string expected option name");
+            return;
+        }
+        let name = tokens.by_ref().take_while(|&token| token != "value").collect::<Vec<_>>().join(" ");
+        let value = tokens.collect::<Vec<_>>().join(" ");
+
+        match name.to_lowercase().as_str() {
+            "hash" => match value.parse::<usize>() {
+                Ok(mb) if (1..=MAX_HASH).contains(&mb) => self.tt = Arc::new(TranspositionTable::new(mb)),
+                _ => println!("info string Hash has to be between 1 and {MAX_HASH}"),
+            },
+            _ => println!("info string unknown option {name}"),
+        }
+    }
+
+    /// `position startpos|fen <fen> [moves <move>...]`
+    fn position<'a>(&mut self, mut tokens: impl Iterator<Item = &'a str>) {
+        let fen = match tokens.next() {
+            Some("startpos") => STARTPOS.to_string(),
+            Some("fen") => tokens.by_ref().take_while(|&token| token != "moves").collect::<Vec<_>>().join(" "),
+            _ => {
+                println!("info string expected startpos or fen");
+                return;
+            }
+        };
+
+        let game = self.game_mut();
+        if let Err(err) = game.set_fen(&fen) {
+            println!("info string bad fen: {err}");
+            return;
+        }
+
+        // after a fen the "moves" token was already eaten by take_while
+        for name in tokens.skip_while(|&token| token == "moves") {
+            match game.parse_uci_move(name) {
+                Ok(mv) => game.make_move(&mv),
+                Err(err) => {
+                    // rather than leave half the moves played, go back to where the GUI started from
+                    println!("info string {err} {name}, position reset to {fen}");
+                    game.set_fen(&fen).expect("fen was just parsed");
+                    return;
+                }
+            }
+        }
+    }
+
+    fn go(&mut self, params: GoParams) {
+        let mut game = self.game.take().expect("position is owned by a running search");
+        let mut pawns = self.pawns.take().expect("pawn table is owned by a running search");
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread_stop = Arc::clone(&stop);
+        let tt = Arc::clone(&self.tt);
+
+        let handle = thread::spawn(move || {
+            let limits = params.limits(game.turn());
+            let result = search::search(&mut game, limits, &tt, &mut pawns, &thread_stop, |result| print_info(result, &tt));
+
+            // an infinite search only reports its move once the GUI asks for it
+            while params.infinite && !thread_stop.load(Ordering::Relaxed) {
+                thread::sleep(Duration::from_millis(1));
+            }
+
+            match result.best_move {
+                Some(mv) => println!("bestmove {mv}"),
+                None => println!("bestmove 0000"),
+            }
+            (game, pawns)
+        });
+
+        self.search = Some(Search { stop, handle });
+    }
+
+    /// halt the running search, if any, and take the position back from it
+    fn stop(&mut self) {
+        if let Some(search) = self.search.take() {
+            search.stop.store(true, Ordering::Relaxed);
+            let (game, pawns) = search.handle.join().expect("search thread panicked");
+            self.game = Some(game);
+            self.pawns = Some(pawns);
+        }
+    }
+}
+
+impl Default for Uci {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn print_info(result: &SearchResult, tt: &TranspositionTable) {
+    let score = match result.score {
+        Score::Centipawns(cp) => format!("cp {cp}"),
+        Score::Mate(moves) => format!("mate {moves}"),
+    };
+    let millis = result.elapsed.as_millis();
+    let nps = result.nodes as u128 * 1000 / millis.max(1);
+    let pv = result.pv.iter().map(|mv| mv.to_uci()).collect::<Vec<_>>().join(" ");
+
+    println!(
+        "info depth {} score {score} nodes {} nps {nps} hashfull {} time {millis} pv {pv}",
+        result.depth,
+        result.nodes,
+        tt.hashfull(),
+    );
+}
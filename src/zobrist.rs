@@ -0,0 +1,59 @@
+// random keys for zobrist hashing, generated at compile time so every build agrees on them
+
+const fn splitmix64(state: u64) -> (u64, u64) {
+    let state = state.wrapping_add(0x9E3779B97F4A7C15);
+    let mut z = state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+    (state, z ^ (z >> 31))
+}
+
+const fn gen_keys<const N: usize>(mut state: u64) -> [u64; N] {
+    let mut keys = [0; N];
+    let mut i = 0;
+    while i < N {
+        let (next, key) = splitmix64(state);
+        state = next;
+        keys[i] = key;
+        i += 1;
+    }
+    keys
+}
+
+const SEED: u64 = 0x6B6E696768746D61;
+
+// one long run of the generator, split into the tables below
+const KEYS: [u64; 2 * 6 * 64 + 1 + 4 + 8] = gen_keys(SEED);
+
+/// key of a piece on a square, indexed by [color][piece type][square index]
+pub const PIECES: [[[u64; 64]; 6]; 2] = {
+    let mut pieces = [[[0; 64]; 6]; 2];
+    let mut i = 0;
+    while i < 2 * 6 * 64 {
+        pieces[i / (6 * 64)][(i / 64) % 6][i % 64] = KEYS[i];
+        i += 1;
+    }
+    pieces
+};
+
+/// xored in while black is to move
+pub const BLACK_TO_MOVE: u64 = KEYS[2 * 6 * 64];
+
+/// one key per castling right: white short, white long, black short, black long
+pub const CASTLING: [u64; 4] = [
+    KEYS[2 * 6 * 64 + 1],
+    KEYS[2 * 6 * 64 + 2],
+    KEYS[2 * 6 * 64 + 3],
+    KEYS[2 * 6 * 64 + 4],
+];
+
+/// en passe keys by file, only used while the capture is actually possible
+pub const EN_PASSE: [u64; 8] = {
+    let mut en_passe = [0; 8];
+    let mut i = 0;
+    while i < 8 {
+        en_passe[i] = KEYS[2 * 6 * 64 + 5 + i];
+        i += 1;
+    }
+    en_passe
+};